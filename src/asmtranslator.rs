@@ -0,0 +1,649 @@
+use std::fs::File;
+use std::io::prelude::*;
+
+use crate::config::*;
+use crate::ctranslator::*;
+use crate::input_source::*;
+use crate::memory_tape::*;
+use crate::optimizer::*;
+use crate::program::*;
+
+// bytes read or written by a single syscall
+pub(crate) const BUFFER_SIZE: usize = 4096;
+
+/// Translates a program to GNU as x86-64 assembly for Linux, which builds with
+/// `as` and `ld` alone: the tape is in `.bss` and I/O goes through syscalls.
+///
+/// The tape starts at %rbx and the head is the index in %r12.
+pub struct AsmTranslator {
+    eof: EofBehavior,
+    cell_width: CellWidth,
+    tape_bounds: TapeBounds,
+    bounds_policy: BoundsPolicy,
+    optimization: OptimizationLevel,
+
+    source: Vec<char>,
+    // created once the program is translated
+    file_name: String,
+    code_lines: Vec<String>,
+    // loops translated so far, which number the labels of the next one
+    loop_count: usize,
+}
+
+// operand size suffix of the instructions and the size of a cell in bytes
+pub(crate) fn get_cell_size(cell_width: CellWidth) -> (&'static str, usize) {
+    match cell_width {
+        CellWidth::Bits8 => ("b", 1),
+        CellWidth::Bits16 => ("w", 2),
+        CellWidth::Bits32 => ("l", 4),
+        CellWidth::Unbounded => ("q", 8),
+    }
+}
+
+// the part of %rax, %rdx and so on as wide as a cell
+fn get_register(cell_width: CellWidth, name: char) -> String {
+    match cell_width {
+        CellWidth::Bits8 => String::from("%") + &name.to_string() + "l",
+        CellWidth::Bits16 => String::from("%") + &name.to_string() + "x",
+        CellWidth::Bits32 => String::from("%e") + &name.to_string() + "x",
+        CellWidth::Unbounded => String::from("%r") + &name.to_string() + "x",
+    }
+}
+
+// the amount added to a cell, cut to the cell width as it wraps anyway
+pub(crate) fn get_cell_amount(cell_width: CellWidth, amount: i32) -> i64 {
+    match cell_width {
+        CellWidth::Bits8 => amount as i8 as i64,
+        CellWidth::Bits16 => amount as i16 as i64,
+        CellWidth::Bits32 | CellWidth::Unbounded => amount as i64,
+    }
+}
+
+// the x86-64 code holds cell indices, including the ones reported just past both ends
+// of the tape, and head moves in immediates and displacements of 32 bits
+pub(crate) fn check_immediates<'a>(tape_bounds: TapeBounds, ops: &[Op]) -> Result<(), TranslateError<'a>> {
+    let fits = |value: isize| i32::try_from(value).is_ok();
+
+    if i32::try_from(tape_bounds.cells_count()).is_err() ||
+       !fits(tape_bounds.lowest.saturating_sub(1)) || !fits(tape_bounds.highest.saturating_add(1)) {
+        return Err(TranslateError::Unsupported("Tape cells past 32-bit indices aren't supported in x86-64 code."));
+    }
+
+    for op in ops {
+        let fits_op = match &op.kind {
+            OpKind::Move(distance) | OpKind::Scan(distance) => fits(*distance),
+            OpKind::MulAdd(targets) => targets.iter().all(|(offset, _)| fits(*offset)),
+            OpKind::Loop(body) => {
+                check_immediates(tape_bounds, body)?;
+                true
+            },
+            _ => true,
+        };
+
+        if !fits_op {
+            return Err(TranslateError::Unsupported("Head moves past 32-bit distances aren't supported in x86-64 code."));
+        }
+    }
+
+    Ok(())
+}
+
+// fix_index, called with an index outside the tape in %rdi, following the bounds policy
+fn get_bounds_str(tape_bounds: TapeBounds, policy: BoundsPolicy) -> String {
+    let cells_count = tape_bounds.cells_count().to_string();
+    let last_cell = (tape_bounds.cells_count() - 1).to_string();
+
+    match policy {
+        BoundsPolicy::Clamp => String::from("\
+# moves the index in %rdi to the nearest end of the tape
+fix_index:
+\ttest %rdi, %rdi
+\tjs 1f
+\tmov $") + &last_cell + ", %rdi
+\tret
+1:\txor %edi, %edi
+\tret
+",
+        BoundsPolicy::Wrap => String::from("\
+# wraps the index in %rdi around the tape
+fix_index:
+\tmov %rdi, %rax
+\tcqo
+\tmov $") + &cells_count + ", %rcx
+\tidiv %rcx
+\tmov %rdx, %rdi
+\ttest %rdi, %rdi
+\tjns 1f
+\tadd %rcx, %rdi
+1:\tret
+",
+        // same message and exit code as the interpreter
+        BoundsPolicy::Abort => String::from("\
+# stops the program as the index in %rdi is outside the tape, with the line
+# and column of the instruction in %r8 and %r9
+fix_index:
+\tmov %rdi, %r14
+\tcall flush_output
+\tlea head_message(%rip), %rsi
+\tmov $line_message - head_message, %edx
+\tcall write_error
+\t# a run of moves goes one way, so the head left the tape right past its edge
+\tmov $") + &(tape_bounds.highest + 1).to_string() + ", %rax
+\ttest %r14, %r14
+\tjns 1f
+\tmov $" + &(tape_bounds.lowest - 1).to_string() + ", %rax
+1:\tcall write_number
+\tlea line_message(%rip), %rsi
+\tmov $column_message - line_message, %edx
+\tcall write_error
+\tmov %r8, %rax
+\tcall write_number
+\tlea column_message(%rip), %rsi
+\tmov $end_message - column_message, %edx
+\tcall write_error
+\tmov %r9, %rax
+\tcall write_number
+\tlea end_message(%rip), %rsi
+\tmov $messages_end - end_message, %edx
+\tcall write_error
+\tmov $60, %eax
+\tmov $4, %edi
+\tsyscall
+
+# writes the number in %rax to stderr
+write_number:
+\tlea number_buffer+24(%rip), %rsi
+\tmov %rax, %r10
+\ttest %rax, %rax
+\tjns 1f
+\tneg %rax
+1:\tmov $10, %rcx
+2:\txor %edx, %edx
+\tdiv %rcx
+\tadd $48, %dl
+\tdec %rsi
+\tmov %dl, (%rsi)
+\ttest %rax, %rax
+\tjnz 2b
+\ttest %r10, %r10
+\tjns 3f
+\tdec %rsi
+\tmovb $45, (%rsi)
+3:\tlea number_buffer+24(%rip), %rdx
+\tsub %rsi, %rdx
+
+# writes %rdx bytes from %rsi to stderr
+write_error:
+\tmov $2, %edi
+\tmov $1, %eax
+\tsyscall
+\tret
+
+\t.section .rodata
+head_message:
+\t.ascii \"Head moved out of the tape to cell \"
+line_message:
+\t.ascii \" at line \"
+column_message:
+\t.ascii \", column \"
+end_message:
+\t.ascii \".\\n\"
+messages_end:
+\t.text
+",
+    }
+}
+
+// buffered I/O through the read and write syscalls
+fn get_io_str() -> String {
+    let buffer_size = BUFFER_SIZE.to_string();
+
+    String::from("\
+# appends the byte in %al to the output, which is written once the buffer is full
+put_byte:
+\tmov output_length(%rip), %rcx
+\tlea output_buffer(%rip), %rdx
+\tmov %al, (%rdx,%rcx)
+\tinc %rcx
+\tmov %rcx, output_length(%rip)
+\tcmp $") + &buffer_size + ", %rcx
+\tjae flush_output
+\tret
+
+# writes the buffered output to stdout
+flush_output:
+\tmov output_length(%rip), %rdx
+\tlea output_buffer(%rip), %rsi
+1:\ttest %rdx, %rdx
+\tjle 2f
+\tmov $1, %eax
+\tmov $1, %edi
+\tsyscall
+\t# the output is dropped when stdout can't be written
+\ttest %rax, %rax
+\tjle 2f
+\tadd %rax, %rsi
+\tsub %rax, %rdx
+\tjmp 1b
+2:\tmovq $0, output_length(%rip)
+\tret
+
+# reads the next input byte into %rax, -1 once the input has ended
+get_byte:
+\tmov input_position(%rip), %rcx
+\tcmp input_length(%rip), %rcx
+\tjb 2f
+\t# the output so far is shown before waiting for input
+\tcall flush_output
+\txor %eax, %eax
+\txor %edi, %edi
+\tlea input_buffer(%rip), %rsi
+\tmov $" + &buffer_size + ", %edx
+\tsyscall
+\ttest %rax, %rax
+\tjle 1f
+\tmov %rax, input_length(%rip)
+\txor %ecx, %ecx
+2:\tlea input_buffer(%rip), %rsi
+\tmovzbq (%rsi,%rcx), %rax
+\tinc %rcx
+\tmov %rcx, input_position(%rip)
+\tret
+1:\tmov $-1, %rax
+\tret
+"
+}
+
+fn get_bss_str(tape_bounds: TapeBounds, cell_width: CellWidth) -> String {
+    let buffer_size = BUFFER_SIZE.to_string();
+    let tape_size = (tape_bounds.cells_count() * get_cell_size(cell_width).1).to_string();
+
+    String::from("\
+\t.bss
+\t.balign 8
+output_length:
+\t.skip 8
+input_position:
+\t.skip 8
+input_length:
+\t.skip 8
+number_buffer:
+\t.skip 24
+output_buffer:
+\t.skip ") + &buffer_size + "
+input_buffer:
+\t.skip " + &buffer_size + "
+tape:
+\t.skip " + &tape_size + "
+"
+}
+
+impl AsmTranslator {
+    pub fn new<'a>(file_name: &str, config: &Config) -> Result<AsmTranslator, &'a str> {
+        check_file_name(file_name)?;
+
+        let asm_translator = AsmTranslator {
+                                eof: config.eof,
+                                cell_width: config.cell_width,
+                                tape_bounds: config.tape_bounds,
+                                bounds_policy: config.bounds_policy,
+                                optimization: config.optimization,
+                                source: Vec::new(),
+                                code_lines: Vec::new(),
+                                loop_count: 0,
+                                file_name: String::from(file_name),
+                            };
+
+        Ok(asm_translator)
+    }
+
+    fn push_line(&mut self, line: &str) {
+        self.code_lines.push(String::from("\t") + line);
+    }
+
+    fn push_label(&mut self, label: &str) {
+        self.code_lines.push(String::from(label) + ":");
+    }
+
+    fn get_cell_str(&self, index_register: &str) -> String {
+        String::from("(%rbx,") + index_register + "," + &get_cell_size(self.cell_width).1.to_string() + ")"
+    }
+
+    // instruction with the operand size of a cell
+    fn push_cell_instruction(&mut self, instruction: &str, operands: &str) {
+        let suffix = get_cell_size(self.cell_width).0;
+        self.push_line(&(String::from(instruction) + suffix + " " + operands));
+    }
+
+    // brings the index in the register back onto the tape when it left it,
+    // the span is reported when the program stops there
+    fn push_bounds_check(&mut self, index_register: &str, span: Span) {
+        let last_cell = (self.tape_bounds.cells_count() - 1).to_string();
+
+        // a negative index is above the last cell when compared unsigned
+        self.push_line(&(String::from("cmp $") + &last_cell + ", " + index_register));
+        self.push_line("jbe 1f");
+        if index_register != "%rdi" {
+            self.push_line(&(String::from("mov ") + index_register + ", %rdi"));
+        }
+
+        if self.bounds_policy == BoundsPolicy::Abort {
+            let (line, column) = span.line_and_column(&self.source);
+            self.push_line(&(String::from("mov $") + &line.to_string() + ", %r8"));
+            self.push_line(&(String::from("mov $") + &column.to_string() + ", %r9"));
+        }
+
+        self.push_line("call fix_index");
+        if index_register != "%rdi" {
+            self.push_line(&(String::from("mov %rdi, ") + index_register));
+        }
+        self.push_label("1");
+    }
+
+    fn act_on_move(&mut self, distance: isize, span: Span) {
+        self.push_line(&(String::from("add $") + &distance.to_string() + ", %r12"));
+        self.push_bounds_check("%r12", span);
+    }
+
+    fn act_on_add(&mut self, amount: i32) {
+        let cell = self.get_cell_str("%r12");
+
+        match get_cell_amount(self.cell_width, amount) {
+            0 => (),
+            1 => self.push_cell_instruction("inc", &cell),
+            -1 => self.push_cell_instruction("dec", &cell),
+            amount => self.push_cell_instruction("add", &(String::from("$") + &amount.to_string() + ", " + &cell)),
+        }
+    }
+
+    fn act_on_clear(&mut self) {
+        let cell = self.get_cell_str("%r12");
+        self.push_cell_instruction("mov", &(String::from("$0, ") + &cell));
+    }
+
+    fn act_on_scan(&mut self, distance: isize, span: Span) {
+        let cell = self.get_cell_str("%r12");
+
+        self.push_cell_instruction("cmp", &(String::from("$0, ") + &cell));
+        self.push_line("je 3f");
+        self.push_label("2");
+        self.act_on_move(distance, span);
+        self.push_cell_instruction("cmp", &(String::from("$0, ") + &cell));
+        self.push_line("jne 2b");
+        self.push_label("3");
+    }
+
+    // adds the current cell times the factor to every target, then clears it
+    fn act_on_mul_add(&mut self, targets: &[(isize, i32)], span: Span) {
+        let counter = self.get_cell_str("%r12");
+
+        // only the low bits of the product are kept, so the counter doesn't need its sign
+        let load = match self.cell_width {
+            CellWidth::Bits8 => String::from("movzbq ") + &counter + ", %r13",
+            CellWidth::Bits16 => String::from("movzwq ") + &counter + ", %r13",
+            CellWidth::Bits32 => String::from("movl ") + &counter + ", %r13d",
+            CellWidth::Unbounded => String::from("movq ") + &counter + ", %r13",
+        };
+        self.push_line(&load);
+        // the loop never runs its body when the counter is zero, so no target is reached
+        self.push_line("test %r13, %r13");
+        self.push_line("jz 4f");
+
+        for (offset, factor) in targets {
+            self.push_line(&(String::from("lea ") + &offset.to_string() + "(%r12), %rdi"));
+            self.push_bounds_check("%rdi", span);
+
+            self.push_line(&(String::from("imul $") + &factor.to_string() + ", %r13, %rax"));
+            let target = self.get_cell_str("%rdi");
+            self.push_cell_instruction("add", &(get_register(self.cell_width, 'a') + ", " + &target));
+        }
+
+        self.push_cell_instruction("mov", &(String::from("$0, ") + &counter));
+        self.push_label("4");
+    }
+
+    fn act_on_loop(&mut self, body: &[Op]) {
+        self.loop_count += 1;
+        let label = String::from("loop") + &self.loop_count.to_string();
+        let cell = self.get_cell_str("%r12");
+
+        // the condition is checked before the first iteration and after every one
+        self.push_cell_instruction("cmp", &(String::from("$0, ") + &cell));
+        self.push_line(&(String::from("je ") + &label + "_end"));
+        self.push_label(&label);
+        self.translate_ops(body);
+        self.push_cell_instruction("cmp", &(String::from("$0, ") + &cell));
+        self.push_line(&(String::from("jne ") + &label));
+        self.push_label(&(label + "_end"));
+    }
+
+    fn act_on_input(&mut self) {
+        let cell = self.get_cell_str("%r12");
+        let register = get_register(self.cell_width, 'a');
+
+        self.push_line("call get_byte");
+        match self.eof {
+            EofBehavior::Zero => {
+                self.push_line("cmp $-1, %rax");
+                self.push_line("jne 1f");
+                self.push_line("xor %eax, %eax");
+                self.push_label("1");
+                self.push_cell_instruction("mov", &(register + ", " + &cell));
+            },
+            // -1 is stored with all bits set
+            EofBehavior::AllOnes => self.push_cell_instruction("mov", &(register + ", " + &cell)),
+            EofBehavior::Unchanged => {
+                self.push_line("cmp $-1, %rax");
+                self.push_line("je 1f");
+                self.push_cell_instruction("mov", &(register + ", " + &cell));
+                self.push_label("1");
+            },
+        }
+    }
+
+    fn act_on_print(&mut self) {
+        // the lowest byte of a cell comes first
+        let cell = self.get_cell_str("%r12");
+        self.push_line(&(String::from("movb ") + &cell + ", %al"));
+        self.push_line("call put_byte");
+    }
+
+    fn translate_ops(&mut self, ops: &[Op]) {
+        for op in ops {
+            match &op.kind {
+                // collapsed runs that cancel out produce no code
+                OpKind::Add(0) | OpKind::Move(0) => (),
+                OpKind::Add(amount) => self.act_on_add(*amount),
+                OpKind::Move(distance) => self.act_on_move(*distance, op.span),
+                OpKind::Loop(body) => self.act_on_loop(body),
+                OpKind::Out => self.act_on_print(),
+                OpKind::In => self.act_on_input(),
+                OpKind::Clear => self.act_on_clear(),
+                OpKind::Scan(distance) => self.act_on_scan(*distance, op.span),
+                OpKind::MulAdd(targets) => self.act_on_mul_add(targets, op.span),
+            };
+        }
+    }
+
+    fn text(&self) -> String {
+        let mut text = String::from("\t.text\n\t.globl _start\n_start:\n\tlea tape(%rip), %rbx\n\tmov $") +
+                       &get_head_start(self.tape_bounds).to_string() + ", %r12\n\n";
+
+        for line in &self.code_lines {
+            text = text + line + "\n";
+        }
+
+        text += "\n\tcall flush_output\n\tmov $60, %eax\n\txor %edi, %edi\n\tsyscall\n\n";
+
+        text + &get_io_str() + "\n" + &get_bounds_str(self.tape_bounds, self.bounds_policy) + "\n" +
+               &get_bss_str(self.tape_bounds, self.cell_width)
+    }
+
+    pub fn translate<'a>(&mut self, command: &str) -> Result<(), TranslateError<'a>> {
+        let program = match Program::parse(command) {
+            Ok(program) => program,
+            Err(error) => return Err(TranslateError::Syntax(error)),
+        };
+
+        // translated cells always wrap
+        let program = optimize(program, self.optimization, self.cell_width, CellOverflow::Wrap, self.bounds_policy);
+        check_immediates(self.tape_bounds, &program.ops)?;
+
+        self.source = program.source.clone();
+        self.code_lines = Vec::new();
+        self.loop_count = 0;
+        self.translate_ops(&program.ops);
+        let text = self.text();
+
+        let mut file = match File::create(&self.file_name) {
+            Ok(file) => file,
+            Err(_) => return Err(TranslateError::Write("Invalid file name.")),
+        };
+
+        match file.write_all(text.as_bytes()) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(TranslateError::Write("Couldn't write to file.")),
+        }
+    }
+}
+
+#[test]
+fn cell_amount_for_width() {
+    assert_eq!(get_cell_amount(CellWidth::Bits8, 300), 44);
+    assert_eq!(get_cell_amount(CellWidth::Bits8, 255), -1);
+    assert_eq!(get_cell_amount(CellWidth::Bits16, 65536), 0);
+    assert_eq!(get_cell_amount(CellWidth::Unbounded, -300), -300);
+}
+
+#[test]
+fn register_for_width() {
+    assert_eq!(get_register(CellWidth::Bits8, 'a'), "%al");
+    assert_eq!(get_register(CellWidth::Bits16, 'd'), "%dx");
+    assert_eq!(get_register(CellWidth::Bits32, 'a'), "%eax");
+    assert_eq!(get_register(CellWidth::Unbounded, 'a'), "%rax");
+}
+
+#[cfg(test)]
+fn translate_to_string(name: &str, command: &str, config: &Config) -> String {
+    let asm_file = std::env::temp_dir().join(String::from("bf_asmtranslator_") + name + ".s");
+
+    let mut translator = AsmTranslator::new(asm_file.to_str().unwrap(), config).unwrap();
+    assert!(translator.translate(command).is_ok());
+
+    let code = std::fs::read_to_string(&asm_file).unwrap();
+    std::fs::remove_file(asm_file).unwrap();
+
+    code
+}
+
+// assembling tests say they are skipped when as or ld is missing
+#[cfg(test)]
+fn skip_without_binutils(test: &str) -> bool {
+    let missing = ["as", "ld"].iter().any(|tool| std::process::Command::new(tool).arg("--version").output().is_err());
+    if missing {
+        eprintln!("skipping {}: there is no as or ld", test);
+    }
+
+    missing
+}
+
+// translates the program, assembles and links it and runs it on the input
+#[cfg(test)]
+fn assemble_and_run(command: &str, config: &Config, input: &[u8]) -> crate::native::NativeRun {
+    use std::process::{Command, Stdio};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // the tests run at the same time, each assembles files of its own
+    static RUN_COUNT: AtomicUsize = AtomicUsize::new(0);
+    let name = String::from("bf_asmtranslator_") + &std::process::id().to_string() + "_" +
+               &RUN_COUNT.fetch_add(1, Ordering::Relaxed).to_string();
+    let asm_file = std::env::temp_dir().join(name.clone() + ".s");
+    let object_file = std::env::temp_dir().join(name.clone() + ".o");
+    let binary = std::env::temp_dir().join(name);
+
+    let mut translator = AsmTranslator::new(asm_file.to_str().unwrap(), config).unwrap();
+    assert!(translator.translate(command).is_ok());
+
+    assert!(Command::new("as").arg("-o").arg(&object_file).arg(&asm_file).status().unwrap().success());
+    assert!(Command::new("ld").arg("-o").arg(&binary).arg(&object_file).status().unwrap().success());
+
+    let mut child = Command::new(&binary).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+    // a program that ends without reading all of its input closes the pipe early
+    let _ = child.stdin.take().unwrap().write_all(input);
+    let output = child.wait_with_output().unwrap();
+
+    std::fs::remove_file(asm_file).unwrap();
+    std::fs::remove_file(object_file).unwrap();
+    std::fs::remove_file(binary).unwrap();
+
+    crate::native::NativeRun { output: output.stdout, errors: output.stderr, exit_code: output.status.code() }
+}
+
+#[test]
+fn assemble_shared_settings() {
+    use crate::native::differential_tests::*;
+
+    if skip_without_binutils("assemble_shared_settings") {
+        return;
+    }
+
+    for (corpus, config) in settings() {
+        compare(corpus, &config, &assemble_and_run);
+    }
+}
+
+#[test]
+fn assemble_clamped_multiply() {
+    use crate::native::differential_tests::*;
+
+    if skip_without_binutils("assemble_clamped_multiply") {
+        return;
+    }
+
+    let config = Config::default();
+    assert_eq!(assemble_and_run("+[-<+>]<.", &config, b""), interpret("+[-<+>]<.", &config, b""));
+}
+
+#[test]
+fn assemble_long_input() {
+    use crate::native::differential_tests::*;
+
+    if skip_without_binutils("assemble_long_input") {
+        return;
+    }
+
+    // the input is longer than the buffer it is read into
+    let input: Vec<u8> = (0..10000).map(|n| (n % 255 + 1) as u8).chain([0]).collect();
+
+    for optimization in [OptimizationLevel::None, OptimizationLevel::Idioms] {
+        let config = Config { optimization, ..Config::default() };
+        assert_eq!(assemble_and_run(",[.,]", &config, &input), interpret(",[.,]", &config, &input));
+    }
+}
+
+#[test]
+fn immediate_limits() {
+    let span = Span { start: 0, end: 1 };
+    let long_move = Op { kind: OpKind::Loop(vec![Op { kind: OpKind::Move(1 << 31), span }]), span };
+
+    assert!(check_immediates(TapeBounds::bidirectional(1 << 30), &[]).is_ok());
+    assert!(matches!(check_immediates(TapeBounds::classic(), &[long_move]), Err(TranslateError::Unsupported(_))));
+    assert!(matches!(check_immediates(TapeBounds { lowest: i32::MIN as isize, highest: 0 }, &[]), Err(TranslateError::Unsupported(_))));
+
+    let asm_file = std::env::temp_dir().join("bf_asmtranslator_tape_too_large.s");
+    let config = Config { tape_bounds: TapeBounds::bidirectional(3_000_000_000), bounds_policy: BoundsPolicy::Abort, ..Config::default() };
+
+    let mut translator = AsmTranslator::new(asm_file.to_str().unwrap(), &config).unwrap();
+    assert!(matches!(translator.translate("+[>+]"), Err(TranslateError::Unsupported(_))));
+    assert!(!asm_file.exists());
+}
+
+#[test]
+fn merged_runs() {
+    let config = Config { optimization: OptimizationLevel::None, ..Config::default() };
+    let code = translate_to_string("merged_runs", "+++>>-<<", &config);
+
+    assert!(code.contains("\taddb $3, (%rbx,%r12,1)\n\tadd $2, %r12\n"));
+    assert!(code.contains("\tdecb (%rbx,%r12,1)\n\tadd $-2, %r12\n"));
+    assert!(code.contains("tape:\n\t.skip 30000\n"));
+}
@@ -0,0 +1,125 @@
+use std::fmt;
+
+use crate::program::*;
+
+#[derive(Debug)]
+pub enum BracketErrorKind {
+    UnmatchedOpen,
+    UnmatchedClose,
+}
+
+/// An unbalanced bracket found while parsing.
+#[derive(Debug)]
+pub struct BracketError {
+    pub kind: BracketErrorKind,
+    /// line and column are counted from 1
+    pub line: usize,
+    pub column: usize,
+    pub source_line: String,
+}
+
+impl BracketError {
+    fn new(kind: BracketErrorKind, command: &[char], position: usize) -> BracketError {
+        let (line, column) = Span { start: position, end: position + 1 }.line_and_column(command);
+        let line_start = position + 1 - column;
+
+        let line_end = command[position..]
+            .iter()
+            .position(|ch| *ch == '\n' || *ch == '\r')
+            .map_or(command.len(), |pos| position + pos);
+
+        BracketError {
+            kind,
+            line,
+            column,
+            source_line: String::from_iter(&command[line_start..line_end]),
+        }
+    }
+
+    fn caret_line(&self) -> String {
+        // keep tabs so the caret lines up with the source line
+        let mut caret: String = self.source_line
+            .chars()
+            .take(self.column - 1)
+            .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+            .collect();
+        caret.push('^');
+
+        caret
+    }
+}
+
+impl fmt::Display for BracketError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let description = match self.kind {
+            BracketErrorKind::UnmatchedOpen => "Unmatched '['",
+            BracketErrorKind::UnmatchedClose => "Unmatched ']'",
+        };
+
+        writeln!(f, "{} at line {}, column {}:", description, self.line, self.column)?;
+        writeln!(f, "{}", self.source_line)?;
+        write!(f, "{}", self.caret_line())
+    }
+}
+
+/// checks that every bracket in command has a matching one
+pub fn validate_brackets(command: &[char]) -> Result<(), BracketError> {
+    let mut opened_brackets: Vec<usize> = Vec::new();
+
+    for (pos, ch) in command.iter().enumerate() {
+        match ch {
+            '[' => opened_brackets.push(pos),
+            ']' if opened_brackets.pop().is_none() => return Err(BracketError::new(BracketErrorKind::UnmatchedClose, command, pos)),
+            _ => (),
+        }
+    }
+
+    // the innermost unclosed bracket is reported
+    match opened_brackets.pop() {
+        Some(open_pos) => Err(BracketError::new(BracketErrorKind::UnmatchedOpen, command, open_pos)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod bracket_validator_tests {
+    use crate::bracket_validator::*;
+
+    fn check(command: &str) -> Result<(), BracketError> {
+        let command: Vec<char> = command.chars().collect();
+        validate_brackets(&command)
+    }
+
+    #[test]
+    fn balanced_brackets() {
+        assert!(check("+[>[-]<]").is_ok());
+        assert!(check("[][[]]").is_ok());
+    }
+
+    #[test]
+    fn unmatched_close() {
+        let error = check("+++\n>>]<").unwrap_err();
+
+        assert!(matches!(error.kind, BracketErrorKind::UnmatchedClose));
+        assert_eq!(error.line, 2);
+        assert_eq!(error.column, 3);
+        assert_eq!(error.source_line, ">>]<");
+    }
+
+    #[test]
+    fn unmatched_open() {
+        let error = check("[]\r\n [[-]\r\n").unwrap_err();
+
+        assert!(matches!(error.kind, BracketErrorKind::UnmatchedOpen));
+        assert_eq!(error.line, 2);
+        assert_eq!(error.column, 2);
+        assert_eq!(error.source_line, " [[-]");
+    }
+
+    #[test]
+    fn error_message() {
+        let error = check("\t+]").unwrap_err();
+
+        assert_eq!(error.to_string(), "Unmatched ']' at line 1, column 3:\n\t+]\n\t ^");
+    }
+}
@@ -0,0 +1,75 @@
+use std::fs::File;
+use std::io::prelude::*;
+
+// C source of a translated program, every loop in a function of its own
+pub struct CCode<'a> {
+    pub cell_type: &'a str,
+    // the tape array and move_head
+    pub tape_str: String,
+    // cell of the array the head starts on
+    pub head_start: isize,
+    // statements of main, indented within it
+    pub main: Vec<String>,
+    // statements of fun1, fun2 and so on, indented within them
+    pub functions: Vec<Vec<String>>,
+}
+
+// statements as lines of a function body
+fn indented(statements: &[String]) -> String {
+    statements.iter().map(|statement| String::from("\t") + statement + "\n").collect()
+}
+
+impl CCode<'_> {
+    fn function_declaration(&self, fun_id: usize) -> String {
+        String::from("void fun") + &fun_id.to_string() + "(" + self.cell_type + "** ptr_ref)"
+    }
+
+    pub fn text(&self) -> String {
+        // memrchr is a GNU extension
+        let mut text = String::from("#define _GNU_SOURCE\n#include <stdint.h>\n#include <stdio.h>\n#include <stdlib.h>\n#include <string.h>\n\n");
+
+        for fun_id in 1..=self.functions.len() {
+            text = text + &self.function_declaration(fun_id) + ";\n";
+        }
+
+        text = text + "\n" + &self.tape_str + "\nint main()\n{\n\t" + self.cell_type + "* ptr = array";
+        if self.head_start != 0 {
+            text = text + " + " + &self.head_start.to_string();
+        }
+        text += ";\n\n";
+
+        text = text + &indented(&self.main) + "\n\treturn 0;\n}\n";
+
+        // a loop function works on its own copy of the head, given back once the loop ends
+        for (index, function) in self.functions.iter().enumerate() {
+            text = text + "\n" + &self.function_declaration(index + 1) + "\n{\n\t" +
+                   self.cell_type + "* ptr = *ptr_ref;\n\n" + &indented(function) + "\n\t*ptr_ref = ptr;\n}\n";
+        }
+
+        text
+    }
+
+    pub fn write_to_file<'b>(&self, file: &mut File) -> Result<(), &'b str> {
+        match file.write_all(self.text().as_bytes()) {
+            Ok(_) => Ok(()),
+            Err(_) => Err("Couldn't write to file."),
+        }
+    }
+}
+
+#[test]
+fn function_per_loop_layout() {
+    let code = CCode {
+        cell_type: "uint8_t",
+        tape_str: String::from("static uint8_t array[10] = {0};\n"),
+        head_start: 5,
+        main: vec![String::from("fun1(&ptr);")],
+        functions: vec![vec![String::from("while (*ptr)"), String::from("{"), String::from("\t--*ptr;"), String::from("}")]],
+    };
+
+    let text = code.text();
+
+    assert!(text.contains("void fun1(uint8_t** ptr_ref);\n"));
+    assert!(text.contains("\tuint8_t* ptr = array + 5;\n\n\tfun1(&ptr);\n"));
+    assert!(text.contains("void fun1(uint8_t** ptr_ref)\n{\n\tuint8_t* ptr = *ptr_ref;\n\n\twhile (*ptr)\n\t{\n\t\t--*ptr;\n\t}\n\n\t*ptr_ref = ptr;\n}\n"));
+}
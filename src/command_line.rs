@@ -0,0 +1,1047 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::process::Stdio;
+use std::time::Duration;
+
+use bf_interpreter::asmtranslator::*;
+use bf_interpreter::config::*;
+use bf_interpreter::coverage::*;
+use bf_interpreter::ctranslator::*;
+use bf_interpreter::debugger::*;
+use bf_interpreter::elfwriter::*;
+use bf_interpreter::input_source::*;
+use bf_interpreter::interpreter::*;
+use bf_interpreter::memory_tape::*;
+use bf_interpreter::native::*;
+use bf_interpreter::optimizer::*;
+use bf_interpreter::profiler::*;
+use bf_interpreter::program::*;
+use bf_interpreter::snapshot::*;
+
+pub const EXIT_SUCCESS: i32 = 0;
+// the brainfuck program is invalid
+pub const EXIT_PROGRAM_ERROR: i32 = 1;
+pub const EXIT_USAGE_ERROR: i32 = 2;
+// a file or the program output couldn't be read or written
+pub const EXIT_IO_ERROR: i32 = 3;
+// the program stopped with an error while running
+pub const EXIT_RUNTIME_ERROR: i32 = 4;
+// the step or time limit stopped the run
+pub const EXIT_LIMIT_EXCEEDED: i32 = 5;
+// the C compiler couldn't build the translated program
+pub const EXIT_COMPILE_ERROR: i32 = 6;
+
+// loops listed by the profile command
+const HOT_LOOPS_IN_REPORT: usize = 10;
+
+const USAGE: &str = "\
+Usage: bf-interpreter [<command> <file> [options]]
+
+Without a command the interactive menu is started.
+
+Commands:
+  run <file>         interpret the program
+  trace <file>       interpret the program printing the tape after every step
+  profile <file>     interpret the program and report how often every loop ran
+  coverage <file>    interpret the program and show which instructions never ran
+  debug <file>       step through the program, type help in the session for the commands
+  resume <snapshot>  continue a run saved with --snapshot or the save command of debug
+  translate <file>   translate the program to C, x86-64 assembly or a Linux executable
+  native <file>      translate the program to C, compile it and run the executable
+  check <file>       only validate the program
+
+Options of the commands that interpret the program:
+  -i <file>          read program input from a file instead of stdin
+  --input-text <text>
+                     use the given text as program input
+  --decimal-input    prompt for every input byte, the default for trace and debug
+  --no-wrap          stop with an error when a cell overflows
+  --json-trace <file>
+                     write a JSON object for every executed instruction, one per line
+  --max-steps <n>    stop the run after n steps (not debug)
+  --timeout <secs>   stop the run after the given number of seconds (not debug)
+
+Options of run, profile and coverage:
+  -O0                run without optimizations, the default for profile and coverage
+  -O1                run with loop optimizations, the default for run
+  -o <file>          write program output to a file instead of stdout, also for resume
+  --csv <file>       write the execution count of every instruction to a CSV file (profile)
+  --html <file>      also write the coverage listing as an HTML page (coverage)
+  -i, --input-text   can be repeated for coverage, which runs the program on every
+                     input and shows the instructions none of the runs executed
+
+Options of run and resume:
+  --snapshot <file>  save the state of the run to a file when it stops
+                     resume reads the saved input first, then -i or stdin
+                     unless the input had already ended
+
+Options of every command but check and resume, which uses the ones of the snapshot:
+  --eof <mode>       what , stores when the input has ended: 0, -1 (or 255)
+                     or unchanged, the default
+  --cells <bits>     cell width: 8, the default, 16, 32 or unbounded
+  --tape <size>      tape of <size> cells around cell 0, or the cells <lowest>:<highest>,
+                     by default 0:29999
+  --bounds <policy>  what happens when the head leaves the tape: clamp, the default,
+                     wrap or abort
+
+Options of translate:
+  -o <file>          output file, by default <file> with a .c or .s extension,
+                     or without its extension for elf
+  --target <target>  c, the default, asm for GNU as x86-64 assembly for Linux,
+                     which builds with as and ld alone, or elf for a static
+                     Linux x86-64 executable, which needs no toolchain
+
+Options of translate and native:
+  -O0                translate every instruction as written
+  -O1                turn clear, multiply and scan loops into single statements and,
+                     with --bounds wrap, fold head moves into cell offsets, the default
+  --layout <layout>  functions, the default, for a C function for every loop,
+                     or nested for loops nested inside main (C only)
+
+Options of native:
+  -i <file>          read program input from a file instead of stdin
+  --input-text <text>
+                     use the given text as program input
+  -o <file>          write program output to a file instead of stdout
+  --cc <compiler>    C compiler to run, by default cc
+  --cflags <flags>   flags of the compiler separated by spaces, by default -O2
+
+Exit codes:
+  0  success
+  1  invalid program or snapshot
+  2  invalid arguments
+  3  a file or the program output couldn't be read or written
+  4  runtime error, like a cell overflow or the head leaving the tape
+  5  the run was stopped by --max-steps or --timeout
+  6  the C compiler failed or couldn't be started
+native exits with the exit code of the compiled program instead of 0 or 4";
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Run,
+    Trace,
+    Profile,
+    Coverage,
+    Debug,
+    Resume,
+    Translate,
+    Native,
+    Check,
+}
+
+impl Command {
+    // every command but translate, native and check interprets the program
+    fn interprets(&self) -> bool {
+        !matches!(self, Command::Translate | Command::Native | Command::Check)
+    }
+
+    // commands that translate the program to C
+    fn translates(&self) -> bool {
+        matches!(self, Command::Translate | Command::Native)
+    }
+
+    // commands that give input to the program
+    fn reads_input(&self) -> bool {
+        self.interprets() || *self == Command::Native
+    }
+
+    // check doesn't use the settings and resume takes them from the snapshot
+    fn accepts_settings(&self) -> bool {
+        !matches!(self, Command::Check | Command::Resume)
+    }
+
+    // commands that run the program without stepping through it
+    fn runs_freely(&self) -> bool {
+        matches!(self, Command::Run | Command::Profile | Command::Coverage)
+    }
+}
+
+// what translate writes
+#[derive(Debug, PartialEq)]
+enum TranslateTarget {
+    C,
+    Assembly,
+    Elf,
+}
+
+impl TranslateTarget {
+    fn from_name(name: &str) -> Option<TranslateTarget> {
+        match name {
+            "c" => Some(TranslateTarget::C),
+            "asm" => Some(TranslateTarget::Assembly),
+            "elf" => Some(TranslateTarget::Elf),
+            _ => None,
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            TranslateTarget::C => ".c",
+            TranslateTarget::Assembly => ".s",
+            TranslateTarget::Elf => "",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum ProgramInput {
+    Stdin,
+    File(String),
+    Text(String),
+    Decimal,
+}
+
+#[derive(Debug, PartialEq)]
+struct Arguments {
+    command: Command,
+    file: String,
+    config: Config,
+    input: ProgramInput,
+    // inputs of the further runs of coverage
+    more_inputs: Vec<ProgramInput>,
+    output_file: Option<String>,
+    csv_file: Option<String>,
+    html_file: Option<String>,
+    json_trace_file: Option<String>,
+    snapshot_file: Option<String>,
+    compiler: CCompiler,
+    target: TranslateTarget,
+}
+
+fn default_output_file(file: &str, new_extension: &str) -> String {
+    let output_file = match file.rsplit_once('.') {
+        // only replace an extension of the file name itself
+        Some((stem, extension)) if !extension.contains('/') && !extension.contains('\\') && !stem.is_empty() =>
+            String::from(stem) + new_extension,
+        _ => String::from(file) + new_extension,
+    };
+
+    // an executable of a program file without extension would replace it
+    if output_file == file { output_file + ".out" } else { output_file }
+}
+
+fn option_value(options: &mut dyn Iterator<Item = &String>, option: &str) -> Result<String, String> {
+    match options.next() {
+        Some(value) => Ok(value.clone()),
+        None => Err(String::from("Missing value for ") + option + "."),
+    }
+}
+
+// coverage runs the program once for every input given
+fn set_input(arguments: &mut Arguments, input: ProgramInput, input_given: &mut bool) {
+    if arguments.command == Command::Coverage && *input_given {
+        arguments.more_inputs.push(input);
+    } else {
+        arguments.input = input;
+    }
+
+    *input_given = true;
+}
+
+fn parse_arguments(args: &[String]) -> Result<Arguments, String> {
+    let (command_name, rest) = match args.split_first() {
+        Some(split) => split,
+        None => return Err(String::from("Missing command.")),
+    };
+
+    let command = match command_name.as_str() {
+        "run" => Command::Run,
+        "trace" => Command::Trace,
+        "profile" => Command::Profile,
+        "coverage" => Command::Coverage,
+        "debug" => Command::Debug,
+        "resume" => Command::Resume,
+        "translate" => Command::Translate,
+        "native" => Command::Native,
+        "check" => Command::Check,
+        _ => return Err(String::from("Unknown command ") + command_name + "."),
+    };
+
+    let (file, options) = match rest.split_first() {
+        Some((file, options)) => (file.clone(), options),
+        None => return Err(String::from("Missing file for ") + command_name + "."),
+    };
+
+    // every step is shown when tracing or debugging, so loops are not replaced
+    let is_stepped = command == Command::Trace || command == Command::Debug;
+    // counts are reported for the instructions as written
+    let is_optimized = !is_stepped && command != Command::Profile && command != Command::Coverage;
+
+    let mut arguments = Arguments {
+        config: Config {
+            optimization: if is_optimized { OptimizationLevel::Idioms } else { OptimizationLevel::None },
+            ..Config::default()
+        },
+        // stdin is used by the session commands when debugging
+        input: if is_stepped { ProgramInput::Decimal } else { ProgramInput::Stdin },
+        more_inputs: Vec::new(),
+        output_file: None,
+        csv_file: None,
+        html_file: None,
+        json_trace_file: None,
+        snapshot_file: None,
+        compiler: CCompiler::default(),
+        target: TranslateTarget::C,
+        command,
+        file,
+    };
+
+    let mut input_given = false;
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        match (option.as_str(), &arguments.command) {
+            ("-O0", command) if command.runs_freely() || command.translates() =>
+                arguments.config.optimization = OptimizationLevel::None,
+            ("-O1", command) if command.runs_freely() || command.translates() =>
+                arguments.config.optimization = OptimizationLevel::Idioms,
+            ("-i", command) if command.reads_input() =>
+                set_input(&mut arguments, ProgramInput::File(option_value(&mut options, option)?), &mut input_given),
+            ("--input-text", command) if command.reads_input() =>
+                set_input(&mut arguments, ProgramInput::Text(option_value(&mut options, option)?), &mut input_given),
+            ("--decimal-input", command) if command.interprets() => set_input(&mut arguments, ProgramInput::Decimal, &mut input_given),
+            ("--eof", command) if command.accepts_settings() => {
+                let name = option_value(&mut options, option)?;
+                arguments.config.eof = match EofBehavior::from_name(&name) {
+                    Some(eof) => eof,
+                    None => return Err(String::from("Invalid value ") + &name + " for " + option + "."),
+                };
+            },
+            ("--cells", command) if command.accepts_settings() => {
+                let name = option_value(&mut options, option)?;
+                arguments.config.cell_width = match CellWidth::from_name(&name) {
+                    Some(cell_width) => cell_width,
+                    None => return Err(String::from("Invalid value ") + &name + " for " + option + "."),
+                };
+            },
+            ("--tape", command) if command.accepts_settings() => {
+                let range = option_value(&mut options, option)?;
+                let bounds = match range.parse::<usize>() {
+                    Ok(size) if size > 0 && size <= MAX_TAPE_CELLS => Some(TapeBounds::bidirectional(size)),
+                    _ => TapeBounds::from_range(&range),
+                };
+
+                arguments.config.tape_bounds = match bounds {
+                    Some(bounds) => bounds,
+                    None => return Err(String::from("Invalid value ") + &range + " for " + option + "."),
+                };
+            },
+            ("--bounds", command) if command.accepts_settings() => {
+                let name = option_value(&mut options, option)?;
+                arguments.config.bounds_policy = match BoundsPolicy::from_name(&name) {
+                    Some(policy) => policy,
+                    None => return Err(String::from("Invalid value ") + &name + " for " + option + "."),
+                };
+            },
+            ("--max-steps", command) if command.interprets() && *command != Command::Debug => {
+                let steps = option_value(&mut options, option)?;
+                arguments.config.step_limit = match steps.parse::<u64>() {
+                    Ok(steps) => Some(steps),
+                    Err(_) => return Err(String::from("Invalid value ") + &steps + " for " + option + "."),
+                };
+            },
+            ("--timeout", command) if command.interprets() && *command != Command::Debug => {
+                let seconds = option_value(&mut options, option)?;
+                let time_limit = seconds.parse::<f64>().ok().and_then(|secs| Duration::try_from_secs_f64(secs).ok());
+
+                arguments.config.time_limit = match time_limit {
+                    Some(time_limit) => Some(time_limit),
+                    None => return Err(String::from("Invalid value ") + &seconds + " for " + option + "."),
+                };
+            },
+            ("--json-trace", command) if command.interprets() =>
+                arguments.json_trace_file = Some(option_value(&mut options, option)?),
+            ("--no-wrap", command) if command.interprets() && command.accepts_settings() => arguments.config.cell_overflow = CellOverflow::Error,
+            ("--snapshot", Command::Run | Command::Resume) => arguments.snapshot_file = Some(option_value(&mut options, option)?),
+            ("--layout", command) if command.translates() => {
+                let name = option_value(&mut options, option)?;
+                arguments.config.c_layout = match CLayout::from_name(&name) {
+                    Some(layout) => layout,
+                    None => return Err(String::from("Invalid value ") + &name + " for " + option + "."),
+                };
+            },
+            ("--target", Command::Translate) => {
+                let name = option_value(&mut options, option)?;
+                arguments.target = match TranslateTarget::from_name(&name) {
+                    Some(target) => target,
+                    None => return Err(String::from("Invalid value ") + &name + " for " + option + "."),
+                };
+            },
+            ("--cc", Command::Native) => arguments.compiler.command = option_value(&mut options, option)?,
+            ("--cflags", Command::Native) =>
+                arguments.compiler.flags = option_value(&mut options, option)?.split_whitespace().map(String::from).collect(),
+            ("--csv", Command::Profile) => arguments.csv_file = Some(option_value(&mut options, option)?),
+            ("--html", Command::Coverage) => arguments.html_file = Some(option_value(&mut options, option)?),
+            ("-o", command) if command.runs_freely() || matches!(command, Command::Translate | Command::Native | Command::Resume) => arguments.output_file = Some(option_value(&mut options, option)?),
+            _ => return Err(String::from("Invalid option ") + option + " for " + command_name + "."),
+        };
+    }
+
+    Ok(arguments)
+}
+
+fn create_input_source(input: &ProgramInput) -> Result<InputSource, i32> {
+    match input {
+        ProgramInput::Stdin => Ok(InputSource::Stdin),
+        ProgramInput::Text(text) => Ok(InputSource::from_bytes(text.as_bytes())),
+        ProgramInput::Decimal => Ok(InputSource::InteractiveDecimal),
+        ProgramInput::File(file_name) => InputSource::from_file(file_name).map_err(|msg| {
+            eprintln!("{}: {}", file_name, msg);
+            EXIT_IO_ERROR
+        }),
+    }
+}
+
+// append adds to the file instead of replacing it
+fn create_output(output_file: &Option<String>, append: bool) -> Result<Box<dyn Write>, i32> {
+    match output_file {
+        Some(file_name) => match File::options().write(true).create(true).append(append).truncate(!append).open(file_name) {
+            Ok(file) => Ok(Box::new(BufWriter::new(file))),
+            Err(_) => {
+                eprintln!("{}: Invalid file name.", file_name);
+                Err(EXIT_IO_ERROR)
+            },
+        },
+        None => Ok(Box::new(io::stdout())),
+    }
+}
+
+fn report_interpret_error(file: &str, error: &InterpretError) -> i32 {
+    // whoever closed the pipe has read all the output they wanted
+    if !matches!(error, InterpretError::Runtime(RuntimeError { error: RuntimeErrorKind::Output(io::ErrorKind::BrokenPipe), .. })) {
+        eprintln!("{}: {}", file, error);
+    }
+
+    match error {
+        InterpretError::Syntax(_) => EXIT_PROGRAM_ERROR,
+        InterpretError::Runtime(RuntimeError { error: RuntimeErrorKind::Output(_) | RuntimeErrorKind::Trace(_), .. }) => EXIT_IO_ERROR,
+        InterpretError::Runtime(_) => EXIT_RUNTIME_ERROR,
+    }
+}
+
+fn report_limit(file: &str, interpreter: &Interpreter, status: RunStatus) -> i32 {
+    let (line, column) = interpreter.current_line_and_column();
+    let steps = interpreter.result().statistics.steps;
+
+    eprintln!("{}: {} at line {}, column {} after {} steps.", file, status, line, column, steps);
+
+    EXIT_LIMIT_EXCEEDED
+}
+
+// append adds the records to the file, for the runs after the first of a command
+fn enable_json_trace(arguments: &Arguments, append: bool, interpreter: &mut Interpreter) -> Result<(), i32> {
+    if let Some(file_name) = &arguments.json_trace_file {
+        match File::options().write(true).create(true).append(append).truncate(!append).open(file_name) {
+            Ok(file) => interpreter.enable_trace(Box::new(BufWriter::new(file))),
+            Err(_) => {
+                eprintln!("{}: Invalid file name.", file_name);
+                return Err(EXIT_IO_ERROR);
+            },
+        };
+    }
+
+    Ok(())
+}
+
+// interpreter writing the JSON trace asked for in the arguments
+fn create_interpreter(arguments: &Arguments,
+                      should_print_tape: bool,
+                      input: InputSource,
+                      output: Box<dyn Write>) -> Result<Interpreter, i32> {
+    let mut interpreter = Interpreter::new(should_print_tape, &arguments.config, input, output);
+    enable_json_trace(arguments, false, &mut interpreter)?;
+
+    Ok(interpreter)
+}
+
+// saves the snapshot asked for with --snapshot, a stopped or failed run is saved too
+fn save_snapshot(arguments: &Arguments, interpreter: &mut Interpreter, exit_code: i32) -> i32 {
+    let file_name = match &arguments.snapshot_file {
+        Some(file_name) => file_name,
+        None => return exit_code,
+    };
+
+    match interpreter.snapshot().write_to_file(file_name) {
+        Ok(_) => exit_code,
+        Err(msg) => {
+            eprintln!("{}: {}", file_name, msg);
+            if exit_code == EXIT_SUCCESS { EXIT_IO_ERROR } else { exit_code }
+        },
+    }
+}
+
+fn run(arguments: &Arguments, command: &str) -> i32 {
+    let input = match create_input_source(&arguments.input) {
+        Ok(input) => input,
+        Err(exit_code) => return exit_code,
+    };
+
+    let output = match create_output(&arguments.output_file, false) {
+        Ok(output) => output,
+        Err(exit_code) => return exit_code,
+    };
+
+    let mut interpreter = match create_interpreter(arguments, false, input, output) {
+        Ok(interpreter) => interpreter,
+        Err(exit_code) => return exit_code,
+    };
+
+    let exit_code = match interpreter.execute(command) {
+        Ok(RunStatus::Finished) => EXIT_SUCCESS,
+        Ok(status) => report_limit(&arguments.file, &interpreter, status),
+        // nothing ran, so there is no snapshot to save
+        Err(error @ InterpretError::Syntax(_)) => return report_interpret_error(&arguments.file, &error),
+        Err(error) => report_interpret_error(&arguments.file, &error),
+    };
+
+    save_snapshot(arguments, &mut interpreter, exit_code)
+}
+
+fn resume(arguments: &Arguments, text: &str) -> i32 {
+    let mut snapshot = match Snapshot::from_text(text) {
+        Ok(snapshot) => snapshot,
+        Err(msg) => {
+            eprintln!("{}: {}", arguments.file, msg);
+            return EXIT_PROGRAM_ERROR;
+        },
+    };
+
+    // the limits count from the start of the resumed run
+    snapshot.config.step_limit = arguments.config.step_limit;
+    snapshot.config.time_limit = arguments.config.time_limit;
+
+    // input that had ended before the snapshot stays ended
+    let input = if snapshot.input_ended {
+        InputSource::from_bytes(b"")
+    } else {
+        match create_input_source(&arguments.input) {
+            Ok(input) => input,
+            Err(exit_code) => return exit_code,
+        }
+    };
+
+    let output = match create_output(&arguments.output_file, false) {
+        Ok(output) => output,
+        Err(exit_code) => return exit_code,
+    };
+
+    let mut interpreter = match Interpreter::from_snapshot(false, &snapshot, input, output) {
+        Ok(interpreter) => interpreter,
+        Err(msg) => {
+            eprintln!("{}: {}", arguments.file, msg);
+            return EXIT_PROGRAM_ERROR;
+        },
+    };
+
+    if let Err(exit_code) = enable_json_trace(arguments, false, &mut interpreter) {
+        return exit_code;
+    }
+
+    let exit_code = match interpreter.run() {
+        Ok(RunStatus::Finished) => EXIT_SUCCESS,
+        Ok(status) => report_limit(&arguments.file, &interpreter, status),
+        Err(error) => report_interpret_error(&arguments.file, &InterpretError::Runtime(error)),
+    };
+
+    save_snapshot(arguments, &mut interpreter, exit_code)
+}
+
+fn trace(arguments: &Arguments, command: &str) -> i32 {
+    let input = match create_input_source(&arguments.input) {
+        Ok(input) => input,
+        Err(exit_code) => return exit_code,
+    };
+
+    // the output is shown together with the tape
+    let mut interpreter = match create_interpreter(arguments, true, input, Box::new(io::sink())) {
+        Ok(interpreter) => interpreter,
+        Err(exit_code) => return exit_code,
+    };
+
+    // interpret already shows where a limit stopped the run
+    match interpreter.interpret(command) {
+        Ok(RunStatus::Finished) => EXIT_SUCCESS,
+        Ok(_) => EXIT_LIMIT_EXCEEDED,
+        Err(error) => report_interpret_error(&arguments.file, &error),
+    }
+}
+
+fn write_profile_csv(profile: &Profile, file_name: &str) -> i32 {
+    let result = File::create(file_name).and_then(|file| {
+        let mut file = BufWriter::new(file);
+        profile.write_csv(&mut file)?;
+        file.flush()
+    });
+
+    match result {
+        Ok(_) => EXIT_SUCCESS,
+        Err(_) => {
+            eprintln!("{}: Couldn't write to file.", file_name);
+            EXIT_IO_ERROR
+        },
+    }
+}
+
+// the profile is also returned when the run stopped early, together with the exit code,
+// append adds the output and the trace to those of an earlier run
+fn run_profiled(arguments: &Arguments,
+                command: &str,
+                input: &ProgramInput,
+                append: bool) -> Result<(Profile, i32), i32> {
+    let program = match Program::parse(command) {
+        Ok(program) => program,
+        Err(error) => return Err(report_interpret_error(&arguments.file, &InterpretError::Syntax(error))),
+    };
+
+    let input = create_input_source(input)?;
+    let output = create_output(&arguments.output_file, append)?;
+
+    let mut interpreter = Interpreter::new(false, &arguments.config, input, output);
+    enable_json_trace(arguments, append, &mut interpreter)?;
+    interpreter.load(&program);
+    interpreter.enable_profiling();
+
+    let exit_code = match interpreter.run() {
+        Ok(RunStatus::Finished) => EXIT_SUCCESS,
+        Ok(status) => report_limit(&arguments.file, &interpreter, status),
+        Err(error) => report_interpret_error(&arguments.file, &InterpretError::Runtime(error)),
+    };
+
+    Ok((interpreter.profile().expect("profiling is enabled"), exit_code))
+}
+
+fn profile(arguments: &Arguments, command: &str) -> i32 {
+    // a stopped run still shows where its time went
+    let (profile, exit_code) = match run_profiled(arguments, command, &arguments.input, false) {
+        Ok(result) => result,
+        Err(exit_code) => return exit_code,
+    };
+
+    eprint!("\n{}", profile.report(HOT_LOOPS_IN_REPORT));
+
+    match &arguments.csv_file {
+        Some(csv_file) if exit_code == EXIT_SUCCESS => write_profile_csv(&profile, csv_file),
+        Some(csv_file) => {
+            write_profile_csv(&profile, csv_file);
+            exit_code
+        },
+        None => exit_code,
+    }
+}
+
+fn coverage(arguments: &Arguments, command: &str) -> i32 {
+    let (profile, mut exit_code) = match run_profiled(arguments, command, &arguments.input, false) {
+        Ok(result) => result,
+        Err(exit_code) => return exit_code,
+    };
+    let mut coverage = Coverage::new(&profile);
+
+    // the output and the trace of every run follow those of the run before
+    for input in &arguments.more_inputs {
+        let (profile, run_exit_code) = match run_profiled(arguments, command, input, true) {
+            Ok(result) => result,
+            Err(exit_code) => return exit_code,
+        };
+
+        coverage.merge(&Coverage::new(&profile));
+        if exit_code == EXIT_SUCCESS {
+            exit_code = run_exit_code;
+        }
+    }
+
+    eprintln!("\n{}\n\n{}", coverage.terminal_listing(), coverage.summary());
+
+    let html_file = match &arguments.html_file {
+        Some(html_file) => html_file,
+        None => return exit_code,
+    };
+
+    match fs::write(html_file, coverage.html_listing(&arguments.file)) {
+        Ok(_) => exit_code,
+        Err(_) => {
+            eprintln!("{}: Couldn't write to file.", html_file);
+            EXIT_IO_ERROR
+        },
+    }
+}
+
+fn debug(arguments: &Arguments, command: &str) -> i32 {
+    let program = match Program::parse(command) {
+        Ok(program) => program,
+        Err(error) => return report_interpret_error(&arguments.file, &InterpretError::Syntax(error)),
+    };
+
+    let input = match create_input_source(&arguments.input) {
+        Ok(input) => input,
+        Err(exit_code) => return exit_code,
+    };
+
+    let mut interpreter = match create_interpreter(arguments, false, input, Box::new(io::stdout())) {
+        Ok(interpreter) => interpreter,
+        Err(exit_code) => return exit_code,
+    };
+    interpreter.load(&program);
+
+    let mut debugger = Debugger::new(interpreter);
+    match debugger.run_session(&mut io::stdin().lock(), &mut io::stdout()) {
+        Ok(_) => EXIT_SUCCESS,
+        Err(_) => EXIT_IO_ERROR,
+    }
+}
+
+fn translate(arguments: &Arguments, command: &str) -> i32 {
+    let output_file = match &arguments.output_file {
+        Some(output_file) => output_file.clone(),
+        None => default_output_file(&arguments.file, arguments.target.extension()),
+    };
+
+    let translated = match arguments.target {
+        TranslateTarget::C => CTranslator::new(&output_file, &arguments.config)
+            .map(|mut translator| translator.translate(command)),
+        TranslateTarget::Assembly => AsmTranslator::new(&output_file, &arguments.config)
+            .map(|mut translator| translator.translate(command)),
+        TranslateTarget::Elf => ElfWriter::new(&output_file, &arguments.config)
+            .map(|mut writer| writer.translate(command)),
+    };
+
+    let result = match translated {
+        Ok(result) => result,
+        Err(msg) => {
+            eprintln!("{}: {}", output_file, msg);
+            return EXIT_IO_ERROR;
+        },
+    };
+
+    match result {
+        Ok(_) => EXIT_SUCCESS,
+        Err(TranslateError::Syntax(error)) => {
+            eprintln!("{}: {}", arguments.file, error);
+            EXIT_PROGRAM_ERROR
+        },
+        Err(TranslateError::Write(msg)) => {
+            eprintln!("{}: {}", output_file, msg);
+            EXIT_IO_ERROR
+        },
+        // the tape or the program is too large for the target
+        Err(TranslateError::Unsupported(msg)) => {
+            eprintln!("{}: {}", arguments.file, msg);
+            EXIT_USAGE_ERROR
+        },
+    }
+}
+
+fn report_native_error(arguments: &Arguments, error: &NativeError) -> i32 {
+    eprintln!("{}: {}", arguments.file, error);
+
+    match error {
+        NativeError::Translate(TranslateError::Syntax(_)) => EXIT_PROGRAM_ERROR,
+        NativeError::Translate(TranslateError::Unsupported(_)) => EXIT_USAGE_ERROR,
+        NativeError::Translate(TranslateError::Write(_)) | NativeError::Run(_) => EXIT_IO_ERROR,
+        NativeError::Compile(_) => EXIT_COMPILE_ERROR,
+    }
+}
+
+fn native(arguments: &Arguments, command: &str) -> i32 {
+    // the executable reads stdin itself
+    let input = match &arguments.input {
+        ProgramInput::File(file_name) => match fs::read(file_name) {
+            Ok(bytes) => Some(bytes),
+            Err(_) => {
+                eprintln!("{}: Couldn't read file.", file_name);
+                return EXIT_IO_ERROR;
+            },
+        },
+        ProgramInput::Text(text) => Some(text.clone().into_bytes()),
+        _ => None,
+    };
+
+    let output = match &arguments.output_file {
+        Some(file_name) => match File::create(file_name) {
+            Ok(file) => Stdio::from(file),
+            Err(_) => {
+                eprintln!("{}: Invalid file name.", file_name);
+                return EXIT_IO_ERROR;
+            },
+        },
+        None => Stdio::inherit(),
+    };
+
+    let program = match NativeProgram::build(command, &arguments.config, &arguments.compiler) {
+        Ok(program) => program,
+        Err(error) => return report_native_error(arguments, &error),
+    };
+
+    match program.run_attached(input.as_deref(), output) {
+        Ok(Some(exit_code)) => {
+            eprintln!("{}: The compiled program exited with status {}.", arguments.file, exit_code);
+            exit_code
+        },
+        Ok(None) => {
+            eprintln!("{}: The compiled program was stopped by a signal.", arguments.file);
+            EXIT_RUNTIME_ERROR
+        },
+        Err(error) => report_native_error(arguments, &error),
+    }
+}
+
+fn check(file: &str, command: &str) -> i32 {
+    match Program::parse(command) {
+        Ok(_) => {
+            println!("{}: OK", file);
+            EXIT_SUCCESS
+        },
+        Err(error) => {
+            eprintln!("{}: {}", file, error);
+            EXIT_PROGRAM_ERROR
+        },
+    }
+}
+
+// returns the process exit code
+pub fn run_command_line(args: &[String]) -> i32 {
+    let arguments = match parse_arguments(args) {
+        Ok(arguments) => arguments,
+        Err(msg) => {
+            eprintln!("{}\n\n{}", msg, USAGE);
+            return EXIT_USAGE_ERROR;
+        },
+    };
+
+    let command = match fs::read_to_string(&arguments.file) {
+        Ok(text) => text,
+        Err(_) => {
+            eprintln!("{}: Couldn't read file.", arguments.file);
+            return EXIT_IO_ERROR;
+        },
+    };
+
+    match arguments.command {
+        Command::Run => run(&arguments, &command),
+        Command::Trace => trace(&arguments, &command),
+        Command::Profile => profile(&arguments, &command),
+        Command::Coverage => coverage(&arguments, &command),
+        Command::Debug => debug(&arguments, &command),
+        Command::Resume => resume(&arguments, &command),
+        Command::Translate => translate(&arguments, &command),
+        Command::Native => native(&arguments, &command),
+        Command::Check => check(&arguments.file, &command),
+    }
+}
+
+#[cfg(test)]
+mod command_line_tests {
+    use crate::command_line::*;
+
+    fn parse(args: &[&str]) -> Result<Arguments, String> {
+        let args: Vec<String> = args.iter().map(|arg| String::from(*arg)).collect();
+        parse_arguments(&args)
+    }
+
+    #[test]
+    fn run_command() {
+        let arguments = parse(&["run", "hello.bf"]).unwrap();
+
+        assert_eq!(arguments.command, Command::Run);
+        assert_eq!(arguments.file, "hello.bf");
+        assert_eq!(arguments.config.optimization, OptimizationLevel::Idioms);
+        assert_eq!(arguments.input, ProgramInput::Stdin);
+
+        let arguments = parse(&["run", "hello.bf", "-O0", "-i", "input.txt"]).unwrap();
+        assert_eq!(arguments.config.optimization, OptimizationLevel::None);
+        assert_eq!(arguments.input, ProgramInput::File(String::from("input.txt")));
+
+        let arguments = parse(&["run", "hello.bf", "-o", "output.txt"]).unwrap();
+        assert_eq!(arguments.output_file, Some(String::from("output.txt")));
+
+        let arguments = parse(&["run", "hello.bf", "--json-trace", "trace.jsonl"]).unwrap();
+        assert_eq!(arguments.json_trace_file, Some(String::from("trace.jsonl")));
+
+        let arguments = parse(&["run", "hello.bf", "--cells", "16", "--no-wrap"]).unwrap();
+        assert_eq!(arguments.config.cell_width, CellWidth::Bits16);
+        assert_eq!(arguments.config.cell_overflow, CellOverflow::Error);
+
+        let arguments = parse(&["run", "hello.bf", "--tape", "-10:10"]).unwrap();
+        assert_eq!(arguments.config.tape_bounds, TapeBounds { lowest: -10, highest: 10 });
+
+        let arguments = parse(&["run", "hello.bf", "--tape", "1000"]).unwrap();
+        assert_eq!(arguments.config.tape_bounds, TapeBounds::bidirectional(1000));
+
+        let arguments = parse(&["run", "hello.bf", "--bounds", "wrap"]).unwrap();
+        assert_eq!(arguments.config.bounds_policy, BoundsPolicy::Wrap);
+
+        let arguments = parse(&["run", "hello.bf", "--max-steps", "1000", "--timeout", "1.5"]).unwrap();
+        assert_eq!(arguments.config.step_limit, Some(1000));
+        assert_eq!(arguments.config.time_limit, Some(Duration::from_millis(1500)));
+    }
+
+    #[test]
+    fn trace_command() {
+        let arguments = parse(&["trace", "hello.bf"]).unwrap();
+
+        assert_eq!(arguments.config.optimization, OptimizationLevel::None);
+        assert_eq!(arguments.input, ProgramInput::Decimal);
+
+        let arguments = parse(&["trace", "hello.bf", "--input-text", "ab"]).unwrap();
+        assert_eq!(arguments.input, ProgramInput::Text(String::from("ab")));
+
+        let arguments = parse(&["debug", "hello.bf", "--tape", "100"]).unwrap();
+        assert_eq!(arguments.config.optimization, OptimizationLevel::None);
+        assert_eq!(arguments.input, ProgramInput::Decimal);
+        assert!(parse(&["debug", "hello.bf", "-o", "out.txt"]).is_err());
+
+        let arguments = parse(&["profile", "hello.bf", "--csv", "counts.csv"]).unwrap();
+        assert_eq!(arguments.config.optimization, OptimizationLevel::None);
+        assert_eq!(arguments.input, ProgramInput::Stdin);
+        assert_eq!(arguments.csv_file, Some(String::from("counts.csv")));
+        assert!(parse(&["run", "hello.bf", "--csv", "counts.csv"]).is_err());
+
+        let arguments = parse(&["coverage", "hello.bf", "--html", "hello.html", "--input-text", "a"]).unwrap();
+        assert_eq!(arguments.config.optimization, OptimizationLevel::None);
+        assert_eq!(arguments.html_file, Some(String::from("hello.html")));
+        assert!(parse(&["coverage", "hello.bf", "--csv", "counts.csv"]).is_err());
+
+        let arguments = parse(&["coverage", "hello.bf", "-i", "a.txt", "--input-text", "b", "-i", "c.txt"]).unwrap();
+        assert_eq!(arguments.input, ProgramInput::File(String::from("a.txt")));
+        assert_eq!(arguments.more_inputs, [ProgramInput::Text(String::from("b")), ProgramInput::File(String::from("c.txt"))]);
+
+        let arguments = parse(&["run", "hello.bf", "-i", "a.txt", "--input-text", "b"]).unwrap();
+        assert_eq!(arguments.input, ProgramInput::Text(String::from("b")));
+        assert!(arguments.more_inputs.is_empty());
+    }
+
+    #[test]
+    fn resume_command() {
+        let arguments = parse(&["run", "hello.bf", "--max-steps", "100", "--snapshot", "hello.snapshot"]).unwrap();
+        assert_eq!(arguments.snapshot_file, Some(String::from("hello.snapshot")));
+
+        let arguments = parse(&["resume", "hello.snapshot", "-i", "input.txt", "-o", "out.txt", "--max-steps", "100"]).unwrap();
+        assert_eq!(arguments.command, Command::Resume);
+        assert_eq!(arguments.input, ProgramInput::File(String::from("input.txt")));
+        assert_eq!(arguments.output_file, Some(String::from("out.txt")));
+        assert_eq!(arguments.config.step_limit, Some(100));
+
+        assert!(parse(&["resume", "hello.snapshot", "--cells", "16"]).is_err());
+        assert!(parse(&["resume", "hello.snapshot", "-O0"]).is_err());
+        assert!(parse(&["profile", "hello.bf", "--snapshot", "hello.snapshot"]).is_err());
+    }
+
+    #[test]
+    fn resume_with_same_limit() {
+        let path = |extension: &str| std::env::temp_dir().join(String::from("bf_resume_limit_") + &std::process::id().to_string() + extension)
+            .to_str().unwrap().to_string();
+        let (program, snapshot, output) = (path(".bf"), path(".snapshot"), path(".out"));
+        fs::write(&program, "+++++[>+<-]>.").unwrap();
+
+        let run_args = ["run", &program, "-O0", "--max-steps", "20", "--snapshot", &snapshot, "-o", &output];
+        assert_eq!(run_command_line(&run_args.map(String::from)), EXIT_LIMIT_EXCEEDED);
+
+        // the resumed run gets 20 steps of its own, enough to end the program
+        let resume_args = ["resume", &snapshot, "--max-steps", "20", "-o", &output];
+        assert_eq!(run_command_line(&resume_args.map(String::from)), EXIT_SUCCESS);
+        assert_eq!(fs::read(&output).unwrap(), [5]);
+
+        for file in [program, snapshot, output] {
+            fs::remove_file(file).unwrap();
+        }
+    }
+
+    #[test]
+    fn coverage_trace_of_every_input() {
+        let path = |extension: &str| std::env::temp_dir().join(String::from("bf_coverage_trace_") + &std::process::id().to_string() + extension)
+            .to_str().unwrap().to_string();
+        let (program, trace, output) = (path(".bf"), path(".jsonl"), path(".out"));
+        fs::write(&program, ",.").unwrap();
+
+        let args = ["coverage", &program, "--input-text", "a", "--input-text", "b", "--json-trace", &trace, "-o", &output];
+        assert_eq!(run_command_line(&args.map(String::from)), EXIT_SUCCESS);
+        assert_eq!(fs::read(&output).unwrap(), b"ab");
+        // two records for each run
+        assert_eq!(fs::read_to_string(&trace).unwrap().lines().count(), 4);
+
+        for file in [program, trace, output] {
+            fs::remove_file(file).unwrap();
+        }
+    }
+
+    #[test]
+    fn translate_command() {
+        let arguments = parse(&["translate", "dir/hello.bf", "-o", "out.c"]).unwrap();
+        assert_eq!(arguments.output_file, Some(String::from("out.c")));
+
+        let arguments = parse(&["translate", "dir/hello.bf"]).unwrap();
+        assert_eq!(arguments.output_file, None);
+        assert_eq!(arguments.target, TranslateTarget::C);
+        assert_eq!(default_output_file("dir/hello.bf", ".c"), "dir/hello.c");
+        assert_eq!(default_output_file("./hello", ".c"), "./hello.c");
+        assert_eq!(arguments.config.eof, EofBehavior::Unchanged);
+
+        let arguments = parse(&["translate", "hello.bf", "--eof", "0"]).unwrap();
+        assert_eq!(arguments.config.eof, EofBehavior::Zero);
+        assert_eq!(arguments.config.optimization, OptimizationLevel::Idioms);
+
+        let arguments = parse(&["translate", "hello.bf", "-O0"]).unwrap();
+        assert_eq!(arguments.config.optimization, OptimizationLevel::None);
+
+        let arguments = parse(&["translate", "hello.bf", "--layout", "nested"]).unwrap();
+        assert_eq!(arguments.config.c_layout, CLayout::Nested);
+        assert!(parse(&["translate", "hello.bf", "--layout", "flat"]).is_err());
+        assert!(parse(&["run", "hello.bf", "--layout", "nested"]).is_err());
+
+        let arguments = parse(&["translate", "hello.bf", "--target", "asm"]).unwrap();
+        assert_eq!(arguments.target, TranslateTarget::Assembly);
+        assert_eq!(default_output_file("hello.bf", arguments.target.extension()), "hello.s");
+        assert!(parse(&["translate", "hello.bf", "--target", "java"]).is_err());
+
+        let arguments = parse(&["translate", "dir/hello.bf", "--target", "elf"]).unwrap();
+        assert_eq!(arguments.target, TranslateTarget::Elf);
+        assert_eq!(default_output_file("dir/hello.bf", arguments.target.extension()), "dir/hello");
+        assert_eq!(default_output_file("dir/hello", arguments.target.extension()), "dir/hello.out");
+        assert!(parse(&["native", "hello.bf", "--target", "asm"]).is_err());
+    }
+
+    #[test]
+    fn native_command() {
+        let arguments = parse(&["native", "hello.bf"]).unwrap();
+        assert_eq!(arguments.command, Command::Native);
+        assert_eq!(arguments.compiler, CCompiler::default());
+        assert_eq!(arguments.config.optimization, OptimizationLevel::Idioms);
+        assert_eq!(arguments.input, ProgramInput::Stdin);
+
+        let arguments = parse(&["native", "hello.bf", "--cc", "clang", "--cflags", "-O3  -march=native", "-O0"]).unwrap();
+        assert_eq!(arguments.compiler.command, "clang");
+        assert_eq!(arguments.compiler.flags, ["-O3", "-march=native"]);
+        assert_eq!(arguments.config.optimization, OptimizationLevel::None);
+
+        let arguments = parse(&["native", "hello.bf", "--cflags", "", "-i", "input.txt", "-o", "out.txt", "--cells", "16"]).unwrap();
+        assert!(arguments.compiler.flags.is_empty());
+        assert_eq!(arguments.input, ProgramInput::File(String::from("input.txt")));
+        assert_eq!(arguments.output_file, Some(String::from("out.txt")));
+        assert_eq!(arguments.config.cell_width, CellWidth::Bits16);
+
+        assert!(parse(&["native", "hello.bf", "--no-wrap"]).is_err());
+        assert!(parse(&["native", "hello.bf", "--max-steps", "10"]).is_err());
+        assert!(parse(&["native", "hello.bf", "--decimal-input"]).is_err());
+        assert!(parse(&["translate", "hello.bf", "--cc", "clang"]).is_err());
+        assert!(parse(&["run", "hello.bf", "--cflags", "-O3"]).is_err());
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        assert!(parse(&["run"]).is_err());
+        assert!(parse(&["check", "a.bf", "-O0"]).is_err());
+        assert!(parse(&["trace", "a.bf", "-O1"]).is_err());
+        assert!(parse(&["translate", "a.bf", "-o"]).is_err());
+        assert!(parse(&["compile", "a.bf"]).is_err());
+        assert!(parse(&["run", "a.bf", "--eof", "1"]).is_err());
+        assert!(parse(&["check", "a.bf", "--eof", "0"]).is_err());
+        assert!(parse(&["run", "a.bf", "--cells", "64"]).is_err());
+        assert!(parse(&["translate", "a.bf", "--no-wrap"]).is_err());
+        assert!(parse(&["run", "a.bf", "--tape", "0"]).is_err());
+        assert!(parse(&["run", "a.bf", "--tape", "10:-10"]).is_err());
+        assert!(parse(&["run", "a.bf", "--tape", "-9223372036854775808:9223372036854775807"]).is_err());
+        assert!(parse(&["translate", "a.bf", "--tape", "18446744073709551615"]).is_err());
+        assert!(parse(&["run", "a.bf", "--bounds", "grow"]).is_err());
+        assert!(parse(&["translate", "a.bf", "--json-trace", "trace.jsonl"]).is_err());
+        assert!(parse(&["run", "a.bf", "--timeout", "-1"]).is_err());
+        assert!(parse(&["translate", "a.bf", "--max-steps", "10"]).is_err());
+    }
+}
@@ -0,0 +1,45 @@
+use crate::ctranslator::*;
+use crate::input_source::*;
+use crate::memory_tape::*;
+use crate::optimizer::*;
+
+use std::time::Duration;
+
+/// Settings shared by the interpreter and the translators.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+    /// Loop optimizations applied before the program is run.
+    pub optimization: OptimizationLevel,
+    /// What `,` stores once the input has ended.
+    pub eof: EofBehavior,
+    pub cell_width: CellWidth,
+    /// Whether cells wrap around or stop the run when they overflow.
+    /// Only the interpreter checks for overflow, translated programs always wrap.
+    pub cell_overflow: CellOverflow,
+    /// Cells the head can reach, the tape grows up to them on demand.
+    pub tape_bounds: TapeBounds,
+    /// What happens when the head moves past the tape bounds.
+    pub bounds_policy: BoundsPolicy,
+    /// Steps after which the interpreter stops the run.
+    pub step_limit: Option<u64>,
+    /// Time after which the interpreter stops the run.
+    pub time_limit: Option<Duration>,
+    /// How the C translator lays out loops.
+    pub c_layout: CLayout,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            optimization: OptimizationLevel::Idioms,
+            eof: EofBehavior::Unchanged,
+            cell_width: CellWidth::Bits8,
+            cell_overflow: CellOverflow::Wrap,
+            tape_bounds: TapeBounds::classic(),
+            bounds_policy: BoundsPolicy::Clamp,
+            step_limit: None,
+            time_limit: None,
+            c_layout: CLayout::FunctionPerLoop,
+        }
+    }
+}
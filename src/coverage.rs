@@ -0,0 +1,186 @@
+use crate::profiler::*;
+
+const EXECUTED_COLOR: &str = "\x1b[32m";
+const MISSED_COLOR: &str = "\x1b[1;31m";
+const COMMENT_COLOR: &str = "\x1b[2m";
+const RESET_COLOR: &str = "\x1b[0m";
+
+const HTML_STYLE: &str = "\
+body { font-family: sans-serif; margin: 2em; }
+pre { font-family: monospace; line-height: 1.4; }
+.executed { background: #c8f0c8; }
+.missed { background: #f5b8b8; font-weight: bold; }
+.comment { color: #999; }";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Mark {
+    Executed,
+    Missed,
+    Comment,
+}
+
+/// Which instructions of a program ever executed.
+#[derive(Clone, Debug)]
+pub struct Coverage {
+    source: Vec<char>,
+    executed: Vec<bool>,
+}
+
+fn is_instruction(c: char) -> bool {
+    "+-<>[].,".contains(c)
+}
+
+fn escape_html(c: char) -> String {
+    match c {
+        '&' => String::from("&amp;"),
+        '<' => String::from("&lt;"),
+        '>' => String::from("&gt;"),
+        '"' => String::from("&quot;"),
+        _ => c.to_string(),
+    }
+}
+
+impl Coverage {
+    pub fn new(profile: &Profile) -> Coverage {
+        Coverage {
+            source: profile.source().to_vec(),
+            executed: profile.position_counts.iter().map(|count| *count > 0).collect(),
+        }
+    }
+
+    /// Adds the instructions executed by another run of the same program.
+    pub fn merge(&mut self, other: &Coverage) {
+        for (executed, other_executed) in self.executed.iter_mut().zip(&other.executed) {
+            *executed |= other_executed;
+        }
+    }
+
+    fn mark(&self, position: usize) -> Mark {
+        if !is_instruction(self.source[position]) {
+            Mark::Comment
+        } else if self.executed[position] {
+            Mark::Executed
+        } else {
+            Mark::Missed
+        }
+    }
+
+    /// Executed and all instructions in the source.
+    pub fn counts(&self) -> (usize, usize) {
+        let instructions = (0..self.source.len()).filter(|pos| self.mark(*pos) != Mark::Comment).count();
+        let executed = (0..self.source.len()).filter(|pos| self.mark(*pos) == Mark::Executed).count();
+
+        (executed, instructions)
+    }
+
+    pub fn summary(&self) -> String {
+        let (executed, instructions) = self.counts();
+        let share = if instructions == 0 { 100.0 } else { executed as f64 * 100.0 / instructions as f64 };
+
+        format!("{} of {} instructions executed ({:.1}%)", executed, instructions, share)
+    }
+
+    // source split into runs of characters with the same mark
+    fn runs(&self) -> Vec<(Mark, String)> {
+        let mut runs: Vec<(Mark, String)> = Vec::new();
+
+        for (position, c) in self.source.iter().enumerate() {
+            let mark = self.mark(position);
+
+            match runs.last_mut() {
+                Some((last_mark, text)) if *last_mark == mark => text.push(*c),
+                _ => runs.push((mark, c.to_string())),
+            }
+        }
+
+        runs
+    }
+
+    /// Source coloured with terminal escape codes, never executed instructions in red.
+    pub fn terminal_listing(&self) -> String {
+        let mut listing = String::new();
+
+        for (mark, text) in self.runs() {
+            let color = match mark {
+                Mark::Executed => EXECUTED_COLOR,
+                Mark::Missed => MISSED_COLOR,
+                Mark::Comment => COMMENT_COLOR,
+            };
+
+            // colors end before a new line so every line can be read on its own
+            for (index, line) in text.split('\n').enumerate() {
+                if index > 0 {
+                    listing += "\n";
+                }
+                if !line.is_empty() {
+                    listing = listing + color + line + RESET_COLOR;
+                }
+            }
+        }
+
+        listing
+    }
+
+    /// Self-contained HTML page with the annotated source.
+    pub fn html_listing(&self, title: &str) -> String {
+        let title: String = title.chars().map(escape_html).collect();
+
+        let mut html = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>") +
+                       &title + " coverage</title>\n<style>\n" + HTML_STYLE + "\n</style>\n</head>\n<body>\n" +
+                       "<h1>" + &title + "</h1>\n<p>" + &self.summary() + ", " +
+                       "<span class=\"executed\">executed</span>, <span class=\"missed\">never executed</span></p>\n<pre>";
+
+        for (mark, text) in self.runs() {
+            let class = match mark {
+                Mark::Executed => "executed",
+                Mark::Missed => "missed",
+                Mark::Comment => "comment",
+            };
+
+            let text: String = text.chars().map(escape_html).collect();
+            html = html + "<span class=\"" + class + "\">" + &text + "</span>";
+        }
+
+        html + "</pre>\n</body>\n</html>\n"
+    }
+}
+
+#[cfg(test)]
+mod coverage_tests {
+    use crate::coverage::*;
+
+    fn coverage(source: &str, position_counts: Vec<u64>) -> Coverage {
+        Coverage::new(&Profile::new(source.chars().collect(), position_counts, Vec::new(), 0))
+    }
+
+    #[test]
+    fn counts() {
+        let coverage = coverage("+[-] a", vec![1, 1, 0, 1, 0, 0]);
+
+        assert_eq!(coverage.counts(), (3, 4));
+        assert_eq!(coverage.summary(), "3 of 4 instructions executed (75.0%)");
+    }
+
+    #[test]
+    fn merge() {
+        let mut first = coverage("+[-]", vec![1, 1, 0, 1]);
+        first.merge(&coverage("+[-]", vec![1, 1, 2, 1]));
+
+        assert_eq!(first.counts(), (4, 4));
+    }
+
+    #[test]
+    fn terminal_listing() {
+        let listing = coverage("+[\n-]x", vec![1, 1, 0, 0, 1, 0]).terminal_listing();
+
+        assert_eq!(listing, String::from("\x1b[32m+[\x1b[0m\n\x1b[1;31m-\x1b[0m\x1b[32m]\x1b[0m\x1b[2mx\x1b[0m"));
+    }
+
+    #[test]
+    fn html_listing() {
+        let html = coverage("+<a&b", vec![1, 0, 0, 0, 0]).html_listing("<test>");
+
+        assert!(html.contains("<title>&lt;test&gt; coverage</title>"));
+        assert!(html.contains("<span class=\"executed\">+</span><span class=\"missed\">&lt;</span><span class=\"comment\">a&amp;b</span>"));
+    }
+}
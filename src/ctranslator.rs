@@ -1,161 +1,667 @@
+use std::fmt;
 use std::fs::File;
+use std::mem;
+use std::path::Path;
+
+use crate::bracket_validator::*;
+use crate::c_code::*;
+use crate::config::*;
+use crate::input_source::*;
+use crate::memory_tape::*;
+use crate::optimizer::*;
+use crate::program::*;
+
+/// How the loops of a program are laid out in C.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CLayout {
+    /// every loop in a function of its own
+    FunctionPerLoop,
+    /// loops nested inside main
+    Nested,
+}
 
-use crate::code_parser::*;
+impl CLayout {
+    pub fn from_name(name: &str) -> Option<CLayout> {
+        match name {
+            "functions" => Some(CLayout::FunctionPerLoop),
+            "nested" => Some(CLayout::Nested),
+            _ => None,
+        }
+    }
+}
 
-pub struct CTranslator {
-    command: Vec<char>,
-    command_pos: usize,
-    whiles_count: usize,
+pub enum TranslateError<'a> {
+    Syntax(BracketError),
+    Write(&'a str),
+    /// the target can't hold the tape or a head move
+    Unsupported(&'a str),
+}
+
+impl fmt::Display for TranslateError<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TranslateError::Syntax(error) => write!(f, "{}", error),
+            TranslateError::Write(msg) | TranslateError::Unsupported(msg) => write!(f, "{}", msg),
+        }
+    }
+}
 
-    file: File,
+pub struct CTranslator {
+    eof: EofBehavior,
+    cell_width: CellWidth,
+    tape_bounds: TapeBounds,
+    bounds_policy: BoundsPolicy,
+    layout: CLayout,
+    optimization: OptimizationLevel,
+
+    source: Vec<char>,
+    // created once the program is translated, so a program that can't be leaves no file
+    file_name: String,
+    // statements of the function being translated
     code_lines: Vec<String>,
+    // loops the statements are nested in within their function
+    depth: usize,
+    // statements of the functions the loops became, fun1 first
+    functions: Vec<Vec<String>>,
+    // moves folded into the offsets of the cells that follow them, and the last of them,
+    // which is reported when a folded cell is outside the tape
+    //
+    // moves are only folded when the head wraps around the tape, as a clamped or aborted
+    // move depends on where the head is when it moves, not only on where it ends up
+    offset: isize,
+    offset_span: Span,
 }
 
-fn get_arithmetic_str(operation: &str, number: usize) -> String{
+fn get_arithmetic_str(cell: &str, operation: &str, number: usize) -> String{
     if number == 1 {
-        String::from(operation) + operation
+        String::from(operation) + operation + cell + ";"
+    } else {
+        String::from(cell) + " " + operation + "= " + &number.to_string() + ";"
+    }
+}
+
+fn get_cell_type(cell_width: CellWidth) -> &'static str {
+    match cell_width {
+        CellWidth::Bits8 => "uint8_t",
+        CellWidth::Bits16 => "uint16_t",
+        CellWidth::Bits32 => "uint32_t",
+        CellWidth::Unbounded => "int64_t",
+    }
+}
+
+fn get_input_str(eof: EofBehavior, cell: &str) -> String {
+    match eof {
+        EofBehavior::Zero => String::from("{ int c = getchar(); ") + cell + " = c == EOF ? 0 : c; }",
+        // EOF is -1, which the cell stores with all bits set
+        EofBehavior::AllOnes => String::from(cell) + " = getchar();",
+        EofBehavior::Unchanged => String::from("{ int c = getchar(); if (c != EOF) ") + cell + " = c; }",
+    }
+}
+
+// checks that a file can be created under the name before anything is translated
+pub(crate) fn check_file_name<'a>(file_name: &str) -> Result<(), &'a str> {
+    let path = Path::new(file_name);
+    let directory = match path.parent() {
+        Some(directory) if !directory.as_os_str().is_empty() => directory,
+        _ => Path::new("."),
+    };
+
+    if file_name.is_empty() || path.is_dir() || !directory.is_dir() {
+        return Err("Invalid file name.");
+    }
+
+    Ok(())
+}
+
+// cell the head starts on, counted from the start of the array
+pub(crate) fn get_head_start(tape_bounds: TapeBounds) -> isize {
+    0.clamp(tape_bounds.lowest, tape_bounds.highest) - tape_bounds.lowest
+}
+
+// the tape array and move_head, which moves the head following the bounds policy
+fn get_tape_str(cell_type: &str, tape_bounds: TapeBounds, policy: BoundsPolicy) -> String {
+    let cells_count = tape_bounds.cells_count().to_string();
+    let last_cell = (tape_bounds.cells_count() - 1).to_string();
+
+    let mut tape = String::from("static ") + cell_type + " array[" + &cells_count + "] = {0};\n\n";
+
+    let parameters = if policy == BoundsPolicy::Abort { "long distance, int line, int column" } else { "long distance" };
+    tape = tape + "static void move_head(" + cell_type + "** ptr, " + parameters + ")\n{\n" +
+           "\tlong index = *ptr - array + distance;\n";
+
+    tape += &match policy {
+        BoundsPolicy::Clamp => String::from("\tif (index < 0) index = 0;\n\tif (index > ") + &last_cell + ") index = " + &last_cell + ";\n",
+        BoundsPolicy::Wrap => String::from("\tindex %= ") + &cells_count + ";\n\tif (index < 0) index += " + &cells_count + ";\n",
+        // same message and exit code as the interpreter
+        BoundsPolicy::Abort => String::from("\tif (index < 0 || index > ") + &last_cell + ")\n\t{\n" +
+                               "\t\tfflush(stdout);\n" +
+                               "\t\tfprintf(stderr, \"Head moved out of the tape to cell %ld at line %d, column %d.\\n\", index < 0 ? " +
+                               &(tape_bounds.lowest - 1).to_string() + "L : " + &(tape_bounds.highest + 1).to_string() + "L, line, column);\n" +
+                               "\t\texit(4);\n\t}\n",
+    };
+
+    tape + "\t*ptr = array + index;\n}\n"
+}
+
+// cell_at, which finds a cell at an offset from the head, and scan, which moves the head
+// to the nearest zero cell, both following the bounds policy like move_head
+//
+// cells at an offset are reached through cell_at rather than as ptr[offset], as the offset
+// may be past an end of the array, where the bounds policy decides the cell, but a cell inside
+// the array costs a single range check
+fn get_idioms_str(cell_type: &str, tape_bounds: TapeBounds, policy: BoundsPolicy) -> String {
+    let cells_count = tape_bounds.cells_count().to_string();
+    let (parameters, arguments) = if policy == BoundsPolicy::Abort {
+        (", int line, int column", ", line, column")
     } else {
-        String::from(" ") + operation + "= " + &number.to_string()
+        ("", "")
+    };
+
+    let mut idioms = String::from("static inline ") + cell_type + "* cell_at(" + cell_type + "* ptr, long offset" + parameters + ")\n{\n" +
+                     "\tlong index = ptr - array + offset;\n" +
+                     "\tif (index >= 0 && index < " + &cells_count + ")\n\t\treturn ptr + offset;\n\n" +
+                     "\tmove_head(&ptr, offset" + arguments + ");\n\treturn ptr;\n}\n\n";
+
+    idioms = idioms + "static void scan(" + cell_type + "** ptr, long distance" + parameters + ")\n{\n" +
+             "\t" + cell_type + "* p = *ptr;\n";
+
+    // memchr and memrchr find zero bytes, which are only zero cells when cells are bytes,
+    // and memrchr is only there with glibc
+    if cell_type == "uint8_t" {
+        idioms = idioms + "\tif (distance == 1)\n\t{\n" +
+                 "\t\tuint8_t* found = memchr(p, 0, " + &cells_count + " - (p - array));\n" +
+                 "\t\tp = found ? found : array + " + &cells_count + " - 1;\n\t}\n" +
+                 "#ifdef __GLIBC__\n" +
+                 "\telse if (distance == -1)\n\t{\n" +
+                 "\t\tuint8_t* found = memrchr(array, 0, p - array + 1);\n" +
+                 "\t\tp = found ? found : array;\n\t}\n" +
+                 "#endif\n";
     }
+
+    // the edge of the tape is left to move_head
+    idioms + "\twhile (*p && p - array + distance >= 0 && p - array + distance < " + &cells_count + ")\n" +
+             "\t\tp += distance;\n" +
+             "\t*ptr = p;\n" +
+             "\twhile (**ptr)\n" +
+             "\t\tmove_head(ptr, distance" + arguments + ");\n}\n"
 }
 
 impl CTranslator {
-    pub fn new(file_name: &str) -> Result<CTranslator, &str> {
-        let ctranslator = CTranslator {
-                            command: Vec::new(),
-                            command_pos: 0,
+    pub fn new<'a>(file_name: &str, config: &Config) -> Result<CTranslator, &'a str> {
+        check_file_name(file_name)?;
 
-                            whiles_count: 0,
+        let ctranslator = CTranslator {
+                            eof: config.eof,
+                            cell_width: config.cell_width,
+                            tape_bounds: config.tape_bounds,
+                            bounds_policy: config.bounds_policy,
+                            layout: config.c_layout,
+                            optimization: config.optimization,
+                            source: Vec::new(),
                             code_lines: Vec::new(),
-
-                            file: match File::create(file_name) {
-                                Ok(file) => file,
-                                Err(_) => return Err("Invalid file name.")
-                            },
+                            depth: 0,
+                            functions: Vec::new(),
+                            offset: 0,
+                            offset_span: Span { start: 0, end: 0 },
+                            file_name: String::from(file_name),
                         };
 
-        return Ok(ctranslator);
+        Ok(ctranslator)
     }
 
-    fn count_symblol(&mut self, symbl: char) -> usize {
-        let mut cnt = 1;
-        self.command_pos += 1;
+    fn push_line(&mut self, line: &str) {
+        self.code_lines.push("\t".repeat(self.depth) + line);
+    }
 
-        loop {
-            if self.command_pos > self.command.len()-1 
-            {
-                // last symbol reached
-                return cnt;
-            }
+    // the position is reported when the head leaves the tape
+    fn get_position_arguments(&self, span: Span) -> String {
+        if self.bounds_policy != BoundsPolicy::Abort {
+            return String::new();
+        }
 
-            let ch = self.command[self.command_pos];
+        let (line, column) = span.line_and_column(&self.source);
+        String::from(", ") + &line.to_string() + ", " + &column.to_string()
+    }
 
-            if ch != symbl {
-                // move back to previous symbol
-                self.command_pos -= 1;
+    // the cell at the given offset from the head, span is reported when it is outside the tape
+    fn get_cell_str(&self, offset: isize, span: Span) -> String {
+        if offset == 0 {
+            String::from("*ptr")
+        } else {
+            String::from("*cell_at(ptr, ") + &offset.to_string() + &self.get_position_arguments(span) + ")"
+        }
+    }
 
-                return cnt;
-            }
+    fn act_on_move(&mut self, distance: isize, span: Span) {
+        let ptr_move = String::from("move_head(&ptr, ") + &distance.to_string() + &self.get_position_arguments(span);
 
-            cnt += 1;
-            self.command_pos += 1;
+        self.push_line(&(ptr_move + ");"));
+    }
+
+    // moves the head by the folded moves
+    fn flush_offset(&mut self) {
+        if self.offset != 0 {
+            self.act_on_move(self.offset, self.offset_span);
+            self.offset = 0;
         }
     }
 
-    fn act_on_right_move(&mut self) {
-        let move_count = self.count_symblol('>');
+    fn act_on_add(&mut self, amount: i32) {
+        let cell = self.get_cell_str(self.offset, self.offset_span);
+
+        let ptr_add = if amount > 0 {
+            get_arithmetic_str(&cell, "+", amount as usize)
+        } else {
+            get_arithmetic_str(&cell, "-", amount.unsigned_abs() as usize)
+        };
 
-        let ptr_increment = String::from("ptr") + &get_arithmetic_str("+", move_count) + ";";
-        self.code_lines.push(ptr_increment);
+        self.push_line(&ptr_add);
     }
 
-    fn act_on_left_move(&mut self) {
-        let move_count = self.count_symblol('<');
+    fn act_on_clear(&mut self) {
+        let cell = self.get_cell_str(self.offset, self.offset_span);
+        self.push_line(&(cell + " = 0;"));
+    }
 
-        let ptr_decrement = String::from("ptr") + &get_arithmetic_str("-", move_count) + ";";
-        self.code_lines.push(ptr_decrement);
+    fn act_on_scan(&mut self, distance: isize, span: Span) {
+        let scan = String::from("scan(&ptr, ") + &distance.to_string() + &self.get_position_arguments(span) + ");";
+        self.push_line(&scan);
     }
 
-    fn act_on_increment(&mut self) {
-        let incr_count = self.count_symblol('+');
+    // adds the current cell times the factor to every target, then clears it
+    fn act_on_mul_add(&mut self, targets: &[(isize, i32)], span: Span) {
+        let counter = self.get_cell_str(self.offset, self.offset_span);
+
+        // the loop never runs its body when the counter is zero, so no target is reached
+        self.push_line(&(String::from("if (") + &counter + ")"));
+        self.push_line("{");
+        self.depth += 1;
+
+        for (offset, factor) in targets {
+            let target = self.get_cell_str(self.offset + offset, span);
+
+            let mul_add = match factor {
+                1 => target + " += " + &counter + ";",
+                -1 => target + " -= " + &counter + ";",
+                _ if *factor < 0 => target + " -= " + &factor.unsigned_abs().to_string() + " * " + &counter + ";",
+                _ => target + " += " + &factor.to_string() + " * " + &counter + ";",
+            };
+            self.push_line(&mul_add);
+        }
 
-        let ptr_increment = String::from("*ptr") + &get_arithmetic_str("+", incr_count) + ";";
-        self.code_lines.push(ptr_increment);
+        self.push_line(&(counter + " = 0;"));
+        self.depth -= 1;
+        self.push_line("}");
     }
 
-    fn act_on_decrement(&mut self) {
-        let decr_count = self.count_symblol('-');
-
-        let ptr_decrement = String::from("*ptr") + &get_arithmetic_str("-", decr_count) + ";";
-        self.code_lines.push(ptr_decrement);
+    fn translate_while(&mut self, body: &[Op]) {
+        self.push_line("while (*ptr)");
+        self.push_line("{");
+        self.depth += 1;
+        self.translate_ops(body);
+        self.depth -= 1;
+        self.push_line("}");
     }
 
-    fn act_on_lbracket(&mut self) {
-        let while_str = String::from("while (*ptr)");
-        self.code_lines.push(while_str);
-        self.code_lines.push(String::from("{"));
-        self.whiles_count += 1;
+    // the loop becomes a function of its own, called where the loop was
+    fn translate_loop_function(&mut self, body: &[Op]) {
+        self.functions.push(Vec::new());
+        let fun_id = self.functions.len();
+        self.push_line(&(String::from("fun") + &fun_id.to_string() + "(&ptr);"));
+
+        let caller_lines = mem::take(&mut self.code_lines);
+        let caller_depth = mem::replace(&mut self.depth, 0);
+
+        self.translate_while(body);
+
+        self.functions[fun_id - 1] = mem::replace(&mut self.code_lines, caller_lines);
+        self.depth = caller_depth;
     }
 
-    fn act_on_rbracket(&mut self) {
-        self.code_lines.push(String::from("}"));
+    fn act_on_loop(&mut self, body: &[Op]) {
+        match self.layout {
+            CLayout::FunctionPerLoop => self.translate_loop_function(body),
+            CLayout::Nested => self.translate_while(body),
+        }
     }
 
     fn act_on_input(&mut self) {
-        self.code_lines.push(String::from("*ptr = getchar();"));
+        let cell = self.get_cell_str(self.offset, self.offset_span);
+        self.push_line(&get_input_str(self.eof, &cell));
     }
 
     fn act_on_print(&mut self) {
-        self.code_lines.push(String::from("putchar(*ptr);"));
-        
-    }
-
-    pub fn translate<'a>(&mut self, command: &str) -> Result<(), &'a str> {
-        self.command = command.chars().collect();
-        
-        self.command_pos = 0;
-        while self.command_pos < self.command.len() {
-            let ch = self.command[self.command_pos];
-
-            match ch {
-                '>' => self.act_on_right_move(),
-                '<' => self.act_on_left_move(),
-                '+' => self.act_on_increment(),
-                '-' => self.act_on_decrement(),
-                '[' => self.act_on_lbracket(),
-                ']' => self.act_on_rbracket(),
-                '.' => self.act_on_print(),
-                ',' => self.act_on_input(),
-                _   => (),
-                };
-
-            self.command_pos += 1;
+        let cell = self.get_cell_str(self.offset, self.offset_span);
+        self.push_line(&(String::from("putchar(") + &cell + ");"));
+    }
+
+    // moves are folded into offsets when optimizing a wrapping tape, loops and scans need the head where it is
+    fn translate_ops(&mut self, ops: &[Op]) {
+        for op in ops {
+            match &op.kind {
+                // collapsed runs that cancel out produce no code
+                OpKind::Add(0) | OpKind::Move(0) => (),
+                OpKind::Add(amount) => self.act_on_add(*amount),
+                OpKind::Move(distance) if self.optimization == OptimizationLevel::Idioms && self.bounds_policy == BoundsPolicy::Wrap => {
+                    self.offset += distance;
+                    self.offset_span = op.span;
+                },
+                OpKind::Move(distance) => self.act_on_move(*distance, op.span),
+                OpKind::Loop(body) => {
+                    self.flush_offset();
+                    self.act_on_loop(body);
+                },
+                OpKind::Out => self.act_on_print(),
+                OpKind::In => self.act_on_input(),
+                OpKind::Clear => self.act_on_clear(),
+                OpKind::Scan(distance) => {
+                    self.flush_offset();
+                    self.act_on_scan(*distance, op.span);
+                },
+                OpKind::MulAdd(targets) => self.act_on_mul_add(targets, op.span),
+            };
+        }
+
+        self.flush_offset();
+    }
+
+    pub fn translate<'a>(&mut self, command: &str) -> Result<(), TranslateError<'a>> {
+        let program = match Program::parse(command) {
+            Ok(program) => program,
+            Err(error) => return Err(TranslateError::Syntax(error)),
+        };
+
+        // translated cells always wrap
+        let program = optimize(program, self.optimization, self.cell_width, CellOverflow::Wrap, self.bounds_policy);
+
+        self.source = program.source.clone();
+        self.code_lines = Vec::new();
+        self.functions = Vec::new();
+        self.translate_ops(&program.ops);
+
+        let cell_type = get_cell_type(self.cell_width);
+        let mut tape_str = get_tape_str(cell_type, self.tape_bounds, self.bounds_policy);
+        if self.optimization == OptimizationLevel::Idioms {
+            tape_str = tape_str + "\n" + &get_idioms_str(cell_type, self.tape_bounds, self.bounds_policy);
         }
 
-        parse(&self.code_lines, self.whiles_count + 1, &mut self.file)
+        let code = CCode {
+            cell_type,
+            tape_str,
+            head_start: get_head_start(self.tape_bounds),
+            main: mem::take(&mut self.code_lines),
+            functions: mem::take(&mut self.functions),
+        };
+
+        let mut file = match File::create(&self.file_name) {
+            Ok(file) => file,
+            Err(_) => return Err(TranslateError::Write("Invalid file name.")),
+        };
+
+        code.write_to_file(&mut file).map_err(TranslateError::Write)
     }
 }
 
 #[test]
 fn increment_with_one() {
-    let result = get_arithmetic_str("+", 1);
-    assert_eq!(result, "++");
+    let result = get_arithmetic_str("*ptr", "+", 1);
+    assert_eq!(result, "++*ptr;");
 }
 
 #[test]
 fn increment() {
-    let result = get_arithmetic_str("+", 7);
-    assert_eq!(result, " += 7");
+    let result = get_arithmetic_str("*ptr", "+", 7);
+    assert_eq!(result, "*ptr += 7;");
 }
 
 #[test]
 fn decrement_with_one() {
-    let result = get_arithmetic_str("-", 1);
-    assert_eq!(result, "--");
+    let result = get_arithmetic_str("*ptr", "-", 1);
+    assert_eq!(result, "--*ptr;");
 }
 
 #[test]
 fn decrement() {
-    let result = get_arithmetic_str("-", 7);
-    assert_eq!(result, " -= 7");
-}
\ No newline at end of file
+    let result = get_arithmetic_str("*cell_at(ptr, 2)", "-", 7);
+    assert_eq!(result, "*cell_at(ptr, 2) -= 7;");
+}
+
+#[test]
+fn no_file_for_syntax_error() {
+    let c_file = std::env::temp_dir().join("bf_ctranslator_syntax_error.c");
+    let _ = std::fs::remove_file(&c_file);
+
+    let mut translator = CTranslator::new(c_file.to_str().unwrap(), &Config::default()).unwrap();
+    assert!(matches!(translator.translate("+[>+"), Err(TranslateError::Syntax(_))));
+    assert!(!c_file.exists());
+
+    assert!(CTranslator::new("", &Config::default()).is_err());
+    assert!(CTranslator::new(std::env::temp_dir().join("bf_missing_directory/a.c").to_str().unwrap(), &Config::default()).is_err());
+}
+
+#[test]
+fn input_str_keeps_cell_on_eof() {
+    let result = get_input_str(EofBehavior::Unchanged, "*ptr");
+    assert_eq!(result, "{ int c = getchar(); if (c != EOF) *ptr = c; }");
+}
+
+#[test]
+fn cell_type_for_width() {
+    assert_eq!(get_cell_type(CellWidth::Bits8), "uint8_t");
+    assert_eq!(get_cell_type(CellWidth::Bits32), "uint32_t");
+}
+
+#[test]
+fn head_start_within_bounds() {
+    assert_eq!(get_head_start(TapeBounds::classic()), 0);
+    assert_eq!(get_head_start(TapeBounds::bidirectional(100)), 50);
+    assert_eq!(get_head_start(TapeBounds { lowest: 5, highest: 10 }), 0);
+}
+
+#[test]
+fn tape_str_for_policy() {
+    let tape = get_tape_str("uint8_t", TapeBounds::classic(), BoundsPolicy::Wrap);
+    assert!(tape.starts_with("static uint8_t array[30000] = {0};"));
+    assert!(tape.contains("index %= 30000;"));
+
+    let tape = get_tape_str("uint8_t", TapeBounds::bidirectional(10), BoundsPolicy::Abort);
+    assert!(tape.contains("move_head(uint8_t** ptr, long distance, int line, int column)"));
+    assert!(tape.contains("index < 0 ? -6L : 5L"));
+}
+
+#[cfg(test)]
+fn translate_to_string(name: &str, command: &str, config: &Config) -> String {
+    let c_file = std::env::temp_dir().join(String::from("bf_ctranslator_") + name + ".c");
+
+    let mut translator = CTranslator::new(c_file.to_str().unwrap(), config).unwrap();
+    assert!(translator.translate(command).is_ok());
+
+    let code = std::fs::read_to_string(&c_file).unwrap();
+    std::fs::remove_file(c_file).unwrap();
+
+    code
+}
+
+// tests compiling C say they are skipped when there is no C compiler
+#[cfg(test)]
+pub(crate) fn skip_without_cc(test: &str) -> bool {
+    let missing = std::process::Command::new("cc").arg("--version").output().is_err();
+    if missing {
+        eprintln!("skipping {}: there is no C compiler cc", test);
+    }
+
+    missing
+}
+
+// translates the program in every layout, with and without optimizations, compiles it
+// with cc and runs it on the input, no outputs when there is no C compiler to check it with
+#[cfg(test)]
+fn compile_and_run_with(name: &str, command: &str, config: &Config, input: &[u8]) -> Vec<Vec<u8>> {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    let mut outputs = Vec::new();
+    if skip_without_cc(name) {
+        return outputs;
+    }
+
+    let variants = [(CLayout::FunctionPerLoop, OptimizationLevel::None), (CLayout::Nested, OptimizationLevel::None),
+                    (CLayout::FunctionPerLoop, OptimizationLevel::Idioms), (CLayout::Nested, OptimizationLevel::Idioms)];
+
+    for (c_layout, optimization) in variants {
+        let config = Config { c_layout, optimization, ..config.clone() };
+        let c_file = std::env::temp_dir().join(String::from("bf_ctranslator_") + name + ".c");
+        let binary = std::env::temp_dir().join(String::from("bf_ctranslator_") + name);
+
+        let mut translator = CTranslator::new(c_file.to_str().unwrap(), &config).unwrap();
+        assert!(translator.translate(command).is_ok());
+
+        let compiled = Command::new("cc").arg("-o").arg(&binary).arg(&c_file).status().unwrap();
+        assert!(compiled.success());
+
+        let mut child = Command::new(&binary).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+        child.stdin.take().unwrap().write_all(input).unwrap();
+        outputs.push(child.wait_with_output().unwrap().stdout);
+
+        std::fs::remove_file(c_file).unwrap();
+        std::fs::remove_file(binary).unwrap();
+    }
+
+    outputs
+}
+
+#[cfg(test)]
+fn compile_and_run(name: &str, command: &str, input: &[u8]) -> Vec<Vec<u8>> {
+    compile_and_run_with(name, command, &Config::default(), input)
+}
+
+#[test]
+fn compile_without_loops() {
+    for output in compile_and_run("no_loops", "+++>++>+<.>.>.<<<+.", b"") {
+        assert_eq!(output, [2, 1, 0, 4]);
+    }
+}
+
+#[test]
+fn compile_single_loop() {
+    for output in compile_and_run("single_loop", "+>+++++[>+<-]>.<<+.", b"") {
+        assert_eq!(output, [5, 2]);
+    }
+}
+
+#[test]
+fn compile_nested_loops() {
+    for output in compile_and_run("nested_loops", "++[>+++[>+<-]<-]>>.<.<.", b"") {
+        assert_eq!(output, [6, 0, 0]);
+    }
+}
+
+#[test]
+fn compile_separate_loops() {
+    for output in compile_and_run("separate_loops", ",[>+<-]>[>++<-],.>.", b"\x03") {
+        assert_eq!(output, [0, 6]);
+    }
+}
+
+#[test]
+fn compile_idioms() {
+    for output in compile_and_run("idioms", "+++++[>+++<-]>[>++>+<<-]>>.<.<.>>>,[-]+.<<<<[>]>.", b"x") {
+        assert_eq!(output, [15, 30, 0, 1, 0]);
+    }
+}
+
+#[test]
+fn compile_scans() {
+    let config = Config { tape_bounds: TapeBounds::bidirectional(8), bounds_policy: BoundsPolicy::Wrap, ..Config::default() };
+
+    // the scan to the right wraps to the left end of the tape
+    for output in compile_and_run_with("scans", "+>+>+>+<<<[>].<.<[<].", &config, b"") {
+        assert_eq!(output, [0, 1, 0]);
+    }
+}
+
+#[test]
+fn compile_clamped_moves() {
+    // the clamped move to the left leaves the head on cell 0, so the cell the + after it changes
+    // is the cell the second + changes minus one
+    for output in compile_and_run("clamped_moves", "<+>+.<.", b"") {
+        assert_eq!(output, [1, 1]);
+    }
+
+    // the multiply loop keeps adding cell 0 to itself while the head is clamped on it
+    for output in compile_and_run("clamped_multiply", "+[-<+>]<.", b"") {
+        assert_eq!(output, [1]);
+    }
+
+    // on a wrapping tape of cells -2 to 1, three cells left of cell 0 is cell 1
+    let config = Config { tape_bounds: TapeBounds::bidirectional(4), bounds_policy: BoundsPolicy::Wrap, ..Config::default() };
+    for output in compile_and_run_with("wrapped_moves", "<<<++>>>+.<<<.", &config, b"") {
+        assert_eq!(output, [1, 2]);
+    }
+}
+
+#[test]
+fn compile_skipped_multiply() {
+    let config = Config { bounds_policy: BoundsPolicy::Abort, ..Config::default() };
+
+    // the loop never runs, so its target left of the tape is never reached
+    for output in compile_and_run_with("skipped_multiply", "[-<+>]+.", &config, b"") {
+        assert_eq!(output, [1]);
+    }
+}
+
+#[test]
+fn compile_abort_position() {
+    use std::process::Command;
+
+    let config = Config { tape_bounds: TapeBounds::bidirectional(4), bounds_policy: BoundsPolicy::Abort, ..Config::default() };
+    let binary = std::env::temp_dir().join("bf_ctranslator_abort_position");
+    if skip_without_cc("abort_position") {
+        return;
+    }
+
+    for optimization in [OptimizationLevel::None, OptimizationLevel::Idioms] {
+        let code = translate_to_string("abort_position", "+\n>>+.", &Config { optimization, ..config.clone() });
+        let c_file = std::env::temp_dir().join("bf_ctranslator_abort_position.c");
+        std::fs::write(&c_file, code).unwrap();
+
+        assert!(Command::new("cc").arg("-o").arg(&binary).arg(&c_file).status().unwrap().success());
+
+        let output = Command::new(&binary).output().unwrap();
+        assert_eq!(output.status.code(), Some(4));
+        assert_eq!(String::from_utf8(output.stderr).unwrap(), "Head moved out of the tape to cell 2 at line 2, column 1.\n");
+
+        std::fs::remove_file(c_file).unwrap();
+        std::fs::remove_file(&binary).unwrap();
+    }
+}
+
+#[test]
+fn optimized_code() {
+    let config = Config { bounds_policy: BoundsPolicy::Wrap, ..Config::default() };
+    let code = translate_to_string("optimized_code", "[-]>>+[->+++<]<[<]", &config);
+
+    assert!(code.contains("\t*ptr = 0;\n\t++*cell_at(ptr, 2);\n"));
+    assert!(code.contains("\tif (*cell_at(ptr, 2))\n\t{\n\t\t*cell_at(ptr, 3) += 3 * *cell_at(ptr, 2);\n\t\t*cell_at(ptr, 2) = 0;\n\t}\n"));
+    assert!(code.contains("\tmove_head(&ptr, 1);\n\tscan(&ptr, -1);\n"));
+    assert!(code.contains("memchr(p, 0, 30000 - (p - array))"));
+    assert!(code.contains("memrchr(array, 0, p - array + 1)"));
+
+    // moves are not folded and multiply loops are kept when the head can be clamped
+    let code = translate_to_string("clamped_code", ">>+[->+++<]", &Config::default());
+    assert!(code.contains("\tmove_head(&ptr, 2);\n\t++*ptr;\n\tfun1(&ptr);\n"));
+    assert!(!code.contains("3 * *ptr"));
+}
+
+#[test]
+fn nested_layout() {
+    let config = Config { c_layout: CLayout::Nested, optimization: OptimizationLevel::None, ..Config::default() };
+    let code = translate_to_string("nested_layout", "+[>[-]<-]", &config);
+
+    assert!(!code.contains("fun1"));
+    assert!(code.contains("\twhile (*ptr)\n\t{\n\t\tmove_head(&ptr, 1);\n\t\twhile (*ptr)\n\t\t{\n\t\t\t--*ptr;\n\t\t}\n"));
+
+    let code = translate_to_string("function_layout", "+[>[-]<-]", &Config { optimization: OptimizationLevel::None, ..Config::default() });
+    assert!(code.contains("\tfun1(&ptr);\n"));
+    assert!(code.contains("\t\tfun2(&ptr);\n"));
+}
@@ -0,0 +1,497 @@
+use crate::interpreter::*;
+use crate::memory_tape::*;
+use crate::program::*;
+
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+
+// cells shown on each side of the head by print without arguments
+const CELLS_AROUND_HEAD: isize = 5;
+// entries of the history, a step takes one plus one for each cell it wrote
+const HISTORY_ENTRIES: usize = 1_000_000;
+
+const HELP: &str = "\
+Commands:
+  step [n]            execute the next n instructions, 1 by default
+  continue            run until a breakpoint or the end of the program
+  back [n]            undo the last n instructions or edits, 1 by default
+  last-write <cell>   go back to before the last instruction that wrote the cell
+  to <position>       run until the instruction at the given position
+  break <position>    stop before the instruction at the given position
+  break #             stop at every # in the program
+  delete <position>   remove a breakpoint, delete # stops stopping at #
+  breakpoints         list the breakpoints
+  print [from [to]]   show the cells from..to, the cells around the head by default
+  set [cell] <value>  set a cell, the one under the head by default
+  head <cell>         move the head to a cell
+  loops               show the loops around the next instruction
+  where               show the next instruction
+  save <file>         write a snapshot of the run, to be continued with resume
+  help                show this list
+  quit                end the session
+
+A position is an offset in the program, starting from 0, or <line>:<column>.
+Going back gives read bytes back to the input, output already shown stays.";
+
+/// Interactive session stepping through a loaded program.
+pub struct Debugger {
+    interpreter: Interpreter,
+    // positions of the instructions to stop before
+    breakpoints: BTreeSet<usize>,
+    // instructions following a #, empty unless break # was used
+    hash_breakpoints: BTreeSet<usize>,
+}
+
+impl Debugger {
+    /// Debugs the program already loaded into the interpreter.
+    pub fn new(mut interpreter: Interpreter) -> Debugger {
+        interpreter.enable_history(HISTORY_ENTRIES);
+
+        Debugger {
+            interpreter,
+            breakpoints: BTreeSet::new(),
+            hash_breakpoints: BTreeSet::new(),
+        }
+    }
+
+    pub fn interpreter(&self) -> &Interpreter {
+        &self.interpreter
+    }
+
+    // parses an offset or line:column into an offset in the source
+    fn parse_position(&self, text: &str) -> Option<usize> {
+        let source = self.interpreter.source();
+
+        let position = match text.split_once(':') {
+            None => text.parse().ok()?,
+            Some((line, column)) => {
+                let line: usize = line.parse().ok()?;
+                let column: usize = column.parse().ok()?;
+
+                let line_start = if line == 1 {
+                    0
+                } else {
+                    source.iter()
+                        .enumerate()
+                        .filter(|(_, c)| **c == '\n')
+                        .nth(line.checked_sub(2)?)?.0 + 1
+                };
+
+                line_start + column.checked_sub(1)?
+            },
+        };
+
+        if position < source.len() {
+            Some(position)
+        } else {
+            None
+        }
+    }
+
+    fn describe_position(&self, position: usize) -> String {
+        let (line, column) = Span { start: position, end: position + 1 }.line_and_column(self.interpreter.source());
+
+        String::from("offset ") + &position.to_string() + " (line " + &line.to_string() + ", column " + &column.to_string() + ")"
+    }
+
+    fn is_breakpoint(&self, position: usize) -> bool {
+        self.breakpoints.contains(&position) || self.hash_breakpoints.contains(&position)
+    }
+
+    // a # stops before the instruction following it
+    fn break_on_hash(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        self.hash_breakpoints = self.interpreter.source().iter()
+            .enumerate()
+            .filter(|(_, c)| **c == '#')
+            .filter_map(|(hash_pos, _)| self.interpreter.instruction_start(hash_pos))
+            .collect();
+
+        if self.hash_breakpoints.is_empty() {
+            writeln!(out, "No # before an instruction in the program.")?;
+        }
+
+        Ok(())
+    }
+
+    fn where_str(&self) -> String {
+        if self.interpreter.is_finished() {
+            return String::from("The program has finished.");
+        }
+
+        let position = self.interpreter.current_position();
+        let tape = self.interpreter.tape();
+
+        String::from("Next instruction '") + &self.interpreter.source()[position].to_string() + "' at " +
+            &self.describe_position(position) + ", head at cell " + &tape.head_position.to_string() +
+            " = " + &tape.get_current_value().to_string()
+    }
+
+    // executes up to steps instructions, stopping at breakpoints when asked to
+    fn advance(&mut self, steps: u64, use_breakpoints: bool, target: Option<usize>, out: &mut dyn Write) -> io::Result<()> {
+        let mut executed = 0;
+
+        while executed < steps {
+            // the instruction the session stopped on is always executed
+            if executed > 0 {
+                let position = self.interpreter.current_position();
+
+                if target == Some(position) {
+                    break;
+                }
+                if use_breakpoints && self.is_breakpoint(position) {
+                    writeln!(out, "Breakpoint at {}.", self.describe_position(position))?;
+                    break;
+                }
+            }
+
+            match self.interpreter.step() {
+                Ok(true) => executed += 1,
+                Ok(false) => break,
+                Err(error) => {
+                    writeln!(out, "{}", error)?;
+                    break;
+                },
+            }
+        }
+
+        if let Err(error) = self.interpreter.flush_output() {
+            writeln!(out, "{}", error)?;
+        }
+        writeln!(out, "{}", self.where_str())
+    }
+
+    fn go_back(&mut self, steps: u64, out: &mut dyn Write) -> io::Result<()> {
+        for _ in 0..steps {
+            if !self.interpreter.step_back() {
+                writeln!(out, "No earlier instruction is remembered.")?;
+                break;
+            }
+        }
+
+        writeln!(out, "{}", self.where_str())
+    }
+
+    // stops right before the write, so it is the next instruction
+    fn go_back_to_write(&mut self, index: isize, out: &mut dyn Write) -> io::Result<()> {
+        match self.interpreter.steps_since_write(index) {
+            Some(steps) => self.go_back(steps as u64, out),
+            None => writeln!(out, "Cell {} was never written in the remembered steps.", index),
+        }
+    }
+
+    fn print_cells(&self, from: isize, to: isize, out: &mut dyn Write) -> io::Result<()> {
+        let tape = self.interpreter.tape();
+        let bounds = tape.bounds();
+
+        for index in from.max(bounds.lowest)..=to.min(bounds.highest) {
+            let head_mark = if index == tape.head_position { "  <- head" } else { "" };
+            writeln!(out, "{:>8}: {}{}", index, tape.get_value_at(index), head_mark)?;
+        }
+
+        Ok(())
+    }
+
+    fn set_cell(&mut self, index: isize, value: i64, out: &mut dyn Write) -> io::Result<()> {
+        let tape = self.interpreter.tape();
+
+        if !tape.bounds().contains(index) {
+            return writeln!(out, "Cell {} is outside the tape.", index);
+        }
+
+        let max_value = tape.width().max_value();
+        if tape.width() != CellWidth::Unbounded && !(0..=max_value).contains(&value) {
+            return writeln!(out, "Cells hold values from 0 to {}.", max_value);
+        }
+
+        self.interpreter.set_cell(index, value);
+
+        Ok(())
+    }
+
+    fn print_loops(&self, out: &mut dyn Write) -> io::Result<()> {
+        let loops = self.interpreter.loop_stack();
+
+        if loops.is_empty() {
+            return writeln!(out, "Not inside a loop.");
+        }
+
+        for (depth, position) in loops.iter().enumerate() {
+            writeln!(out, "{:>4}: [ at {}", depth, self.describe_position(*position))?;
+        }
+
+        Ok(())
+    }
+
+    fn print_breakpoints(&self, out: &mut dyn Write) -> io::Result<()> {
+        if self.breakpoints.is_empty() && self.hash_breakpoints.is_empty() {
+            return writeln!(out, "No breakpoints.");
+        }
+
+        for position in &self.breakpoints {
+            writeln!(out, "Breakpoint at {}", self.describe_position(*position))?;
+        }
+        if !self.hash_breakpoints.is_empty() {
+            writeln!(out, "Breakpoint at every #")?;
+        }
+
+        Ok(())
+    }
+
+    /// Executes one command, returns false when the session should end.
+    pub fn execute_command(&mut self, line: &str, out: &mut dyn Write) -> io::Result<bool> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+
+        let (command, arguments) = match words.split_first() {
+            Some((command, arguments)) => (*command, arguments),
+            // an empty line repeats nothing, only shows where the session is
+            None => {
+                writeln!(out, "{}", self.where_str())?;
+                return Ok(true);
+            },
+        };
+
+        let numbers: Vec<i64> = arguments.iter().filter_map(|word| word.parse().ok()).collect();
+        if !["to", "break", "delete", "save"].contains(&command) && numbers.len() != arguments.len() {
+            writeln!(out, "Invalid argument for {}.", command)?;
+            return Ok(true);
+        }
+
+        match (command, arguments.len()) {
+            ("step" | "s", 0) => self.advance(1, false, None, out)?,
+            ("step" | "s", 1) if numbers[0] > 0 => self.advance(numbers[0] as u64, false, None, out)?,
+            ("continue" | "c", 0) => self.advance(u64::MAX, true, None, out)?,
+            ("back", 0) => self.go_back(1, out)?,
+            ("back", 1) if numbers[0] > 0 => self.go_back(numbers[0] as u64, out)?,
+            ("last-write", 1) => self.go_back_to_write(numbers[0] as isize, out)?,
+            ("to", 1) => match self.parse_position(arguments[0]) {
+                Some(position) => {
+                    let target = self.interpreter.instruction_start(position);
+                    self.advance(u64::MAX, true, target, out)?;
+                },
+                None => writeln!(out, "Invalid position {}.", arguments[0])?,
+            },
+            ("break" | "b", 1) if arguments[0] == "#" => self.break_on_hash(out)?,
+            ("delete" | "d", 1) if arguments[0] == "#" => self.hash_breakpoints.clear(),
+            ("break" | "b" | "delete" | "d", 1) => {
+                let position = self.parse_position(arguments[0]).and_then(|pos| self.interpreter.instruction_start(pos));
+
+                match position {
+                    Some(position) if command.starts_with('b') => {
+                        self.breakpoints.insert(position);
+                        writeln!(out, "Breakpoint at {}.", self.describe_position(position))?;
+                    },
+                    Some(position) => {
+                        self.breakpoints.remove(&position);
+                    },
+                    None => writeln!(out, "Invalid position {}.", arguments[0])?,
+                }
+            },
+            ("breakpoints", 0) => self.print_breakpoints(out)?,
+            ("print" | "p", 0) => {
+                let head = self.interpreter.tape().head_position;
+                self.print_cells(head - CELLS_AROUND_HEAD, head + CELLS_AROUND_HEAD, out)?;
+            },
+            ("print" | "p", 1) => self.print_cells(numbers[0] as isize, numbers[0] as isize, out)?,
+            ("print" | "p", 2) => self.print_cells(numbers[0] as isize, numbers[1] as isize, out)?,
+            ("set", 1) => {
+                let head = self.interpreter.tape().head_position;
+                self.set_cell(head, numbers[0], out)?;
+            },
+            ("set", 2) => self.set_cell(numbers[0] as isize, numbers[1], out)?,
+            ("head", 1) => {
+                let index = numbers[0] as isize;
+
+                if self.interpreter.tape().bounds().contains(index) {
+                    self.interpreter.move_head(index);
+                } else {
+                    writeln!(out, "Cell {} is outside the tape.", index)?;
+                }
+            },
+            ("loops", 0) => self.print_loops(out)?,
+            ("where" | "w", 0) => writeln!(out, "{}", self.where_str())?,
+            ("save", 1) => match self.interpreter.snapshot().write_to_file(arguments[0]) {
+                Ok(_) => writeln!(out, "Snapshot written to {}.", arguments[0])?,
+                Err(msg) => writeln!(out, "{}: {}", arguments[0], msg)?,
+            },
+            ("help" | "h", 0) => writeln!(out, "{}", HELP)?,
+            ("quit" | "q", 0) => return Ok(false),
+            _ => writeln!(out, "Invalid command, type help for the list of commands.")?,
+        };
+
+        Ok(true)
+    }
+
+    /// Reads commands until quit or the end of the input.
+    pub fn run_session(&mut self, commands: &mut dyn BufRead, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "{}", self.where_str())?;
+
+        loop {
+            write!(out, "(debug) ")?;
+            out.flush()?;
+
+            let mut line = String::new();
+            if commands.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+
+            if !self.execute_command(&line, out)? {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod debugger_tests {
+    use crate::config::*;
+    use crate::debugger::*;
+    use crate::input_source::*;
+    use crate::optimizer::*;
+    use crate::snapshot::*;
+
+    fn debug(command: &str) -> Debugger {
+        let config = Config { optimization: OptimizationLevel::None, ..Config::default() };
+
+        let mut interpreter = Interpreter::new(false, &config, InputSource::from_bytes(b""), Box::new(io::sink()));
+        interpreter.load(&Program::parse(command).unwrap());
+
+        Debugger::new(interpreter)
+    }
+
+    fn send(debugger: &mut Debugger, commands: &[&str]) -> String {
+        let mut out = Vec::new();
+
+        for command in commands {
+            debugger.execute_command(command, &mut out).unwrap();
+        }
+
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn step_and_continue() {
+        let mut debugger = debug("+>++>+++");
+
+        send(&mut debugger, &["step", "step 2"]);
+        assert_eq!(debugger.interpreter().tape().get_value_at(1), 2);
+        assert_eq!(debugger.interpreter().current_position(), 4);
+
+        let out = send(&mut debugger, &["continue"]);
+        assert!(debugger.interpreter().is_finished());
+        assert!(out.contains("finished"));
+    }
+
+    #[test]
+    fn breakpoints() {
+        let mut debugger = debug("+[>+#+<-]");
+
+        send(&mut debugger, &["break #", "continue"]);
+        assert_eq!(debugger.interpreter().current_position(), 5);
+        assert_eq!(debugger.interpreter().tape().get_value_at(1), 1);
+
+        send(&mut debugger, &["delete #", "break 1:8", "continue"]);
+        assert_eq!(debugger.interpreter().current_position(), 7);
+
+        send(&mut debugger, &["delete 7", "continue"]);
+        assert!(debugger.interpreter().is_finished());
+        assert_eq!(debugger.interpreter().tape().get_value_at(1), 2);
+    }
+
+    #[test]
+    fn run_to_position() {
+        let mut debugger = debug("++\n[->+<]");
+
+        send(&mut debugger, &["to 2:5"]);
+        assert_eq!(debugger.interpreter().current_position(), 7);
+        assert_eq!(debugger.interpreter().loop_stack(), [3]);
+    }
+
+    #[test]
+    fn edit_tape() {
+        let mut debugger = debug("[-]");
+
+        let out = send(&mut debugger, &["set 4 7", "head 4", "set 300", "print 4"]);
+        assert!(out.contains("Cells hold values from 0 to 255."));
+        assert!(out.contains("4: 7  <- head"));
+
+        send(&mut debugger, &["set 3", "continue"]);
+        assert_eq!(debugger.interpreter().tape().get_value_at(4), 0);
+    }
+
+    #[test]
+    fn undo_edits() {
+        let mut debugger = debug("+>+");
+
+        send(&mut debugger, &["step", "set 4 7", "head 4", "back"]);
+        assert_eq!(debugger.interpreter().tape().head_position, 0);
+        assert_eq!(debugger.interpreter().tape().get_value_at(4), 7);
+
+        send(&mut debugger, &["back"]);
+        assert_eq!(debugger.interpreter().tape().get_value_at(4), 0);
+        assert_eq!(debugger.interpreter().result().statistics.steps, 1);
+
+        send(&mut debugger, &["back"]);
+        assert_eq!(debugger.interpreter().tape().get_value_at(0), 0);
+        assert_eq!(debugger.interpreter().result().statistics.steps, 0);
+    }
+
+    #[test]
+    fn loops() {
+        let mut debugger = debug("+[>+[-]<-]");
+
+        let out = send(&mut debugger, &["loops", "step 5", "loops"]);
+        assert!(out.contains("Not inside a loop."));
+        assert!(out.contains("0: [ at offset 1 (line 1, column 2)"));
+        assert!(out.contains("1: [ at offset 4 (line 1, column 5)"));
+    }
+
+    #[test]
+    fn go_back() {
+        let mut debugger = debug("+>++<[->+<]");
+
+        send(&mut debugger, &["continue", "back 2"]);
+        assert_eq!(debugger.interpreter().current_position(), 9);
+        assert_eq!(debugger.interpreter().tape().get_value_at(1), 3);
+
+        let out = send(&mut debugger, &["last-write 0"]);
+        assert!(out.contains("Next instruction '-'"));
+        assert_eq!(debugger.interpreter().tape().get_value_at(0), 1);
+        assert_eq!(debugger.interpreter().tape().get_value_at(1), 2);
+
+        let out = send(&mut debugger, &["last-write 5"]);
+        assert!(out.contains("Cell 5 was never written"));
+        assert_eq!(debugger.interpreter().current_position(), 6);
+
+        let out = send(&mut debugger, &["back 100"]);
+        assert!(out.contains("No earlier instruction is remembered."));
+        assert_eq!(debugger.interpreter().current_position(), 0);
+    }
+
+    #[test]
+    fn save_snapshot() {
+        let mut debugger = debug("++[->+<]");
+        let file_name = std::env::temp_dir().join("bf_debugger_snapshot_test.txt");
+        let file_name = file_name.to_str().unwrap();
+
+        let out = send(&mut debugger, &["step 6", &(String::from("save ") + file_name)]);
+        assert!(out.contains("Snapshot written to"));
+
+        let snapshot = Snapshot::read_from_file(file_name).unwrap();
+        assert_eq!(snapshot.position, debugger.interpreter().current_position());
+        assert_eq!(snapshot.tape, [1, 1]);
+
+        std::fs::remove_file(file_name).unwrap();
+    }
+
+    #[test]
+    fn session() {
+        let mut debugger = debug("+++");
+        let mut out = Vec::new();
+
+        debugger.run_session(&mut "step\nbogus\nquit\nstep\n".as_bytes(), &mut out).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("Invalid command"));
+        assert_eq!(debugger.interpreter().result().statistics.steps, 1);
+    }
+}
@@ -0,0 +1,686 @@
+use std::fs::File;
+use std::io::prelude::*;
+
+use crate::asmtranslator::*;
+use crate::config::*;
+use crate::ctranslator::*;
+use crate::input_source::*;
+use crate::machine_code::*;
+use crate::memory_tape::*;
+use crate::optimizer::*;
+use crate::program::*;
+
+// the headers and the code are loaded here, followed by the messages of the runtime
+const CODE_ADDRESS: u32 = 0x400000;
+// the zeroed segment with the tape and the I/O buffers
+const DATA_ADDRESS: u32 = 0x10000000;
+
+const ELF_HEADER_SIZE: usize = 64;
+const PROGRAM_HEADER_SIZE: usize = 56;
+const HEADERS_SIZE: usize = ELF_HEADER_SIZE + 2 * PROGRAM_HEADER_SIZE;
+
+// variables of the runtime at the start of the data segment
+const OUTPUT_LENGTH: u32 = DATA_ADDRESS;
+const INPUT_POSITION: u32 = DATA_ADDRESS + 8;
+// input_length comes right after input_position
+const NUMBER_BUFFER: u32 = DATA_ADDRESS + 24;
+const OUTPUT_BUFFER: u32 = DATA_ADDRESS + 48;
+const INPUT_BUFFER: u32 = OUTPUT_BUFFER + BUFFER_SIZE as u32;
+const TAPE: u32 = INPUT_BUFFER + BUFFER_SIZE as u32;
+
+// registers by their number in instructions
+const RAX: u8 = 0;
+const RDI: u8 = 7;
+const R12: u8 = 12;
+const R13: u8 = 13;
+
+const HEAD_MESSAGE: &[u8] = b"Head moved out of the tape to cell ";
+const LINE_MESSAGE: &[u8] = b" at line ";
+const COLUMN_MESSAGE: &[u8] = b", column ";
+const END_MESSAGE: &[u8] = b".\n";
+
+/// Writes a program as a static Linux x86-64 ELF executable, which needs neither
+/// libc nor a toolchain to run.
+///
+/// The code does what the one of `AsmTranslator` does, the tape starts at %rbx
+/// and the head is the index in %r12.
+pub struct ElfWriter {
+    eof: EofBehavior,
+    cell_width: CellWidth,
+    tape_bounds: TapeBounds,
+    bounds_policy: BoundsPolicy,
+    optimization: OptimizationLevel,
+
+    source: Vec<char>,
+    // created once the program is translated
+    file_name: String,
+    code: MachineCode,
+    // labels of the routines of the runtime
+    put_byte: usize,
+    get_byte: usize,
+    flush_output: usize,
+    fix_index: usize,
+}
+
+fn program_header(flags: u32, file_size: u64, address: u32, memory_size: u64) -> Vec<u8> {
+    let mut header = Vec::new();
+
+    // loadable segment
+    header.extend_from_slice(&1u32.to_le_bytes());
+    header.extend_from_slice(&flags.to_le_bytes());
+    // both segments start at the beginning of the file, the data segment takes none of it
+    header.extend_from_slice(&0u64.to_le_bytes());
+    header.extend_from_slice(&(address as u64).to_le_bytes());
+    header.extend_from_slice(&(address as u64).to_le_bytes());
+    header.extend_from_slice(&file_size.to_le_bytes());
+    header.extend_from_slice(&memory_size.to_le_bytes());
+    header.extend_from_slice(&0x1000u64.to_le_bytes());
+
+    header
+}
+
+// ELF header and program headers of an executable with the code right after them
+fn get_headers(code_size: usize, data_size: u64) -> Vec<u8> {
+    let file_size = (HEADERS_SIZE + code_size) as u64;
+    let entry = (CODE_ADDRESS as usize + HEADERS_SIZE) as u64;
+
+    // 64 bits, little endian, version 1, System V
+    let mut headers = vec![0x7F, b'E', b'L', b'F', 2, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    // executable for x86-64
+    headers.extend_from_slice(&2u16.to_le_bytes());
+    headers.extend_from_slice(&0x3Eu16.to_le_bytes());
+    headers.extend_from_slice(&1u32.to_le_bytes());
+    headers.extend_from_slice(&entry.to_le_bytes());
+    // program headers follow the ELF header, there are no sections
+    headers.extend_from_slice(&(ELF_HEADER_SIZE as u64).to_le_bytes());
+    headers.extend_from_slice(&0u64.to_le_bytes());
+    headers.extend_from_slice(&0u32.to_le_bytes());
+    headers.extend_from_slice(&(ELF_HEADER_SIZE as u16).to_le_bytes());
+    headers.extend_from_slice(&(PROGRAM_HEADER_SIZE as u16).to_le_bytes());
+    headers.extend_from_slice(&2u16.to_le_bytes());
+    headers.extend_from_slice(&[0; 6]);
+
+    // readable and executable code, readable and writable data
+    headers.extend(program_header(5, file_size, CODE_ADDRESS, file_size));
+    headers.extend(program_header(6, 0, DATA_ADDRESS, data_size));
+
+    headers
+}
+
+// the file is created executable where files have a mode
+#[cfg(unix)]
+fn create_executable(file_name: &str) -> std::io::Result<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    std::fs::OpenOptions::new().write(true).create(true).truncate(true).mode(0o755).open(file_name)
+}
+
+#[cfg(not(unix))]
+fn create_executable(file_name: &str) -> std::io::Result<File> {
+    File::create(file_name)
+}
+
+impl ElfWriter {
+    pub fn new<'a>(file_name: &str, config: &Config) -> Result<ElfWriter, &'a str> {
+        check_file_name(file_name)?;
+
+        let elf_writer = ElfWriter {
+                            eof: config.eof,
+                            cell_width: config.cell_width,
+                            tape_bounds: config.tape_bounds,
+                            bounds_policy: config.bounds_policy,
+                            optimization: config.optimization,
+                            source: Vec::new(),
+                            code: MachineCode::default(),
+                            put_byte: 0,
+                            get_byte: 0,
+                            flush_output: 0,
+                            fix_index: 0,
+                            file_name: String::from(file_name),
+                        };
+
+        Ok(elf_writer)
+    }
+
+    // emits an instruction on the cell at the index in the register, with the operand
+    // size of a cell, reg is the register or opcode extension of the ModRM byte
+    fn emit_cell_instruction(&mut self, opcode8: u8, opcode: u8, reg: u8, index: u8) {
+        let scale = get_cell_size(self.cell_width).1;
+
+        if self.cell_width == CellWidth::Bits16 {
+            self.code.emit(&[0x66]);
+        }
+
+        let rex = 0x40 | if self.cell_width == CellWidth::Unbounded { 0x08 } else { 0 } |
+                         if reg >= 8 { 0x04 } else { 0 } |
+                         if index >= 8 { 0x02 } else { 0 };
+        if rex != 0x40 {
+            self.code.emit(&[rex]);
+        }
+
+        self.code.emit(&[if self.cell_width == CellWidth::Bits8 { opcode8 } else { opcode }]);
+        self.emit_cell_operand(reg, index, scale);
+    }
+
+    // ModRM and SIB bytes of the cell at %rbx plus the index times the scale
+    fn emit_cell_operand(&mut self, reg: u8, index: u8, scale: usize) {
+        let scale_bits = scale.trailing_zeros() as u8;
+        self.code.emit(&[((reg & 7) << 3) | 4, (scale_bits << 6) | ((index & 7) << 3) | 3]);
+    }
+
+    // an immediate as wide as a cell, 32 bits for unbounded cells
+    fn emit_cell_immediate(&mut self, value: i64) {
+        match self.cell_width {
+            CellWidth::Bits8 => self.code.emit(&(value as i8).to_le_bytes()),
+            CellWidth::Bits16 => self.code.emit(&(value as i16).to_le_bytes()),
+            CellWidth::Bits32 | CellWidth::Unbounded => self.code.emit_i32(value as i32),
+        }
+    }
+
+    // compares the current cell with zero
+    fn emit_cell_test(&mut self) {
+        self.emit_cell_instruction(0x80, 0x83, 7, R12);
+        self.code.emit(&[0]);
+    }
+
+    // brings the index in %r12 or %rdi back onto the tape when it left it,
+    // the span is reported when the program stops there
+    fn emit_bounds_check(&mut self, index: u8, span: Span) {
+        let last_cell = (self.tape_bounds.cells_count() - 1) as i32;
+        let on_tape = self.code.new_label();
+
+        // cmp index, last_cell then jbe, a negative index is above the last cell when compared unsigned
+        self.code.emit(if index == R12 { &[0x49, 0x81, 0xFC] } else { &[0x48, 0x81, 0xFF] });
+        self.code.emit_i32(last_cell);
+        self.code.emit_jump(&[0x0F, 0x86], on_tape);
+
+        if index == R12 {
+            // mov rdi, r12
+            self.code.emit(&[0x4C, 0x89, 0xE7]);
+        }
+
+        if self.bounds_policy == BoundsPolicy::Abort {
+            let (line, column) = span.line_and_column(&self.source);
+            // mov r8, line and mov r9, column
+            self.code.emit(&[0x49, 0xC7, 0xC0]);
+            self.code.emit_i32(line as i32);
+            self.code.emit(&[0x49, 0xC7, 0xC1]);
+            self.code.emit_i32(column as i32);
+        }
+
+        self.code.emit_jump(&[0xE8], self.fix_index);
+        if index == R12 {
+            // mov r12, rdi
+            self.code.emit(&[0x49, 0x89, 0xFC]);
+        }
+        self.code.bind(on_tape);
+    }
+
+    fn act_on_move(&mut self, distance: isize, span: Span) {
+        // add r12, distance
+        self.code.emit(&[0x49, 0x81, 0xC4]);
+        self.code.emit_i32(distance as i32);
+        self.emit_bounds_check(R12, span);
+    }
+
+    fn act_on_add(&mut self, amount: i32) {
+        match get_cell_amount(self.cell_width, amount) {
+            0 => (),
+            1 => self.emit_cell_instruction(0xFE, 0xFF, 0, R12),
+            -1 => self.emit_cell_instruction(0xFE, 0xFF, 1, R12),
+            amount => {
+                self.emit_cell_instruction(0x80, 0x81, 0, R12);
+                self.emit_cell_immediate(amount);
+            },
+        }
+    }
+
+    fn act_on_clear(&mut self) {
+        self.emit_cell_instruction(0xC6, 0xC7, 0, R12);
+        self.emit_cell_immediate(0);
+    }
+
+    fn act_on_scan(&mut self, distance: isize, span: Span) {
+        let (start, end) = (self.code.new_label(), self.code.new_label());
+
+        self.emit_cell_test();
+        self.code.emit_jump(&[0x0F, 0x84], end);
+        self.code.bind(start);
+        self.act_on_move(distance, span);
+        self.emit_cell_test();
+        self.code.emit_jump(&[0x0F, 0x85], start);
+        self.code.bind(end);
+    }
+
+    // adds the current cell times the factor to every target, then clears it
+    fn act_on_mul_add(&mut self, targets: &[(isize, i32)], span: Span) {
+        let scale = get_cell_size(self.cell_width).1;
+
+        // the counter goes to %r13 without its sign, only the low bits of the product are kept
+        let load: &[u8] = match self.cell_width {
+            CellWidth::Bits8 => &[0x4E, 0x0F, 0xB6],
+            CellWidth::Bits16 => &[0x4E, 0x0F, 0xB7],
+            CellWidth::Bits32 => &[0x46, 0x8B],
+            CellWidth::Unbounded => &[0x4E, 0x8B],
+        };
+        self.code.emit(load);
+        self.emit_cell_operand(R13, R12, scale);
+
+        // the loop never runs its body when the counter is zero, so no target is reached
+        // test r13, r13; jz done
+        let done = self.code.new_label();
+        self.code.emit(&[0x4D, 0x85, 0xED]);
+        self.code.emit_jump(&[0x0F, 0x84], done);
+
+        for (offset, factor) in targets {
+            // lea rdi, [r12 + offset]
+            self.code.emit(&[0x49, 0x8D, 0xBC, 0x24]);
+            self.code.emit_i32(*offset as i32);
+            self.emit_bounds_check(RDI, span);
+
+            // imul rax, r13, factor
+            self.code.emit(&[0x49, 0x69, 0xC5]);
+            self.code.emit_i32(*factor);
+            self.emit_cell_instruction(0x00, 0x01, RAX, RDI);
+        }
+
+        self.act_on_clear();
+        self.code.bind(done);
+    }
+
+    fn act_on_loop(&mut self, body: &[Op]) {
+        let (start, end) = (self.code.new_label(), self.code.new_label());
+
+        // the condition is checked before the first iteration and after every one
+        self.emit_cell_test();
+        self.code.emit_jump(&[0x0F, 0x84], end);
+        self.code.bind(start);
+        self.translate_ops(body);
+        self.emit_cell_test();
+        self.code.emit_jump(&[0x0F, 0x85], start);
+        self.code.bind(end);
+    }
+
+    fn act_on_input(&mut self) {
+        let skip = self.code.new_label();
+
+        self.code.emit_jump(&[0xE8], self.get_byte);
+        // cmp rax, -1
+        self.code.emit(&[0x48, 0x83, 0xF8, 0xFF]);
+
+        match self.eof {
+            EofBehavior::Zero => {
+                self.code.emit_jump(&[0x0F, 0x85], skip);
+                // xor eax, eax
+                self.code.emit(&[0x31, 0xC0]);
+                self.code.bind(skip);
+                self.emit_cell_instruction(0x88, 0x89, RAX, R12);
+            },
+            // -1 is stored with all bits set
+            EofBehavior::AllOnes => self.emit_cell_instruction(0x88, 0x89, RAX, R12),
+            EofBehavior::Unchanged => {
+                self.code.emit_jump(&[0x0F, 0x84], skip);
+                self.emit_cell_instruction(0x88, 0x89, RAX, R12);
+                self.code.bind(skip);
+            },
+        }
+    }
+
+    fn act_on_print(&mut self) {
+        // mov al, byte [cell], the lowest byte of a cell comes first
+        self.code.emit(&[0x42, 0x8A]);
+        self.emit_cell_operand(RAX, R12, get_cell_size(self.cell_width).1);
+        self.code.emit_jump(&[0xE8], self.put_byte);
+    }
+
+    fn translate_ops(&mut self, ops: &[Op]) {
+        for op in ops {
+            match &op.kind {
+                // collapsed runs that cancel out produce no code
+                OpKind::Add(0) | OpKind::Move(0) => (),
+                OpKind::Add(amount) => self.act_on_add(*amount),
+                OpKind::Move(distance) => self.act_on_move(*distance, op.span),
+                OpKind::Loop(body) => self.act_on_loop(body),
+                OpKind::Out => self.act_on_print(),
+                OpKind::In => self.act_on_input(),
+                OpKind::Clear => self.act_on_clear(),
+                OpKind::Scan(distance) => self.act_on_scan(*distance, op.span),
+                OpKind::MulAdd(targets) => self.act_on_mul_add(targets, op.span),
+            };
+        }
+    }
+
+    // buffered I/O through the read and write syscalls, like in the assembly translation
+    fn emit_io_routines(&mut self) {
+        let code = &mut self.code;
+
+        // put_byte appends the byte in al to the output, which is written once the buffer is full
+        code.bind(self.put_byte);
+        // mov esi, output_length; mov rcx, [rsi]; mov edx, output_buffer; mov [rdx + rcx], al
+        code.emit(&[0xBE]);
+        code.emit_u32(OUTPUT_LENGTH);
+        code.emit(&[0x48, 0x8B, 0x0E, 0xBA]);
+        code.emit_u32(OUTPUT_BUFFER);
+        code.emit(&[0x88, 0x04, 0x0A]);
+        // inc rcx; mov [rsi], rcx; cmp rcx, BUFFER_SIZE; jae flush_output; ret
+        code.emit(&[0x48, 0xFF, 0xC1, 0x48, 0x89, 0x0E, 0x48, 0x81, 0xF9]);
+        code.emit_u32(BUFFER_SIZE as u32);
+        code.emit_jump(&[0x0F, 0x83], self.flush_output);
+        code.emit(&[0xC3]);
+
+        // flush_output writes the buffered output to stdout
+        let (write_loop, written) = (code.new_label(), code.new_label());
+        code.bind(self.flush_output);
+        // mov ecx, output_length; mov rdx, [rcx]; mov esi, output_buffer
+        code.emit(&[0xB9]);
+        code.emit_u32(OUTPUT_LENGTH);
+        code.emit(&[0x48, 0x8B, 0x11, 0xBE]);
+        code.emit_u32(OUTPUT_BUFFER);
+        code.bind(write_loop);
+        // test rdx, rdx; jle written
+        code.emit(&[0x48, 0x85, 0xD2]);
+        code.emit_jump(&[0x0F, 0x8E], written);
+        // mov eax, 1; mov edi, 1; syscall
+        code.emit(&[0xB8, 1, 0, 0, 0, 0xBF, 1, 0, 0, 0, 0x0F, 0x05]);
+        // test rax, rax; jle written, the output is dropped when stdout can't be written
+        code.emit(&[0x48, 0x85, 0xC0]);
+        code.emit_jump(&[0x0F, 0x8E], written);
+        // add rsi, rax; sub rdx, rax; jmp write_loop
+        code.emit(&[0x48, 0x01, 0xC6, 0x48, 0x29, 0xC2]);
+        code.emit_jump(&[0xE9], write_loop);
+        code.bind(written);
+        // mov ecx, output_length; mov qword [rcx], 0; ret
+        code.emit(&[0xB9]);
+        code.emit_u32(OUTPUT_LENGTH);
+        code.emit(&[0x48, 0xC7, 0x01, 0, 0, 0, 0, 0xC3]);
+
+        // get_byte reads the next input byte into rax, -1 once the input has ended
+        let (buffered, ended) = (code.new_label(), code.new_label());
+        code.bind(self.get_byte);
+        // mov esi, input_position; mov rcx, [rsi]; cmp rcx, [rsi + 8]; jb buffered
+        code.emit(&[0xBE]);
+        code.emit_u32(INPUT_POSITION);
+        code.emit(&[0x48, 0x8B, 0x0E, 0x48, 0x3B, 0x4E, 0x08]);
+        code.emit_jump(&[0x0F, 0x82], buffered);
+        // the output so far is shown before waiting for input
+        code.emit_jump(&[0xE8], self.flush_output);
+        // xor eax, eax; xor edi, edi; mov esi, input_buffer; mov edx, BUFFER_SIZE; syscall
+        code.emit(&[0x31, 0xC0, 0x31, 0xFF, 0xBE]);
+        code.emit_u32(INPUT_BUFFER);
+        code.emit(&[0xBA]);
+        code.emit_u32(BUFFER_SIZE as u32);
+        code.emit(&[0x0F, 0x05]);
+        // test rax, rax; jle ended
+        code.emit(&[0x48, 0x85, 0xC0]);
+        code.emit_jump(&[0x0F, 0x8E], ended);
+        // mov esi, input_position; mov [rsi + 8], rax; xor ecx, ecx
+        code.emit(&[0xBE]);
+        code.emit_u32(INPUT_POSITION);
+        code.emit(&[0x48, 0x89, 0x46, 0x08, 0x31, 0xC9]);
+        code.bind(buffered);
+        // mov edx, input_buffer; movzx eax, byte [rdx + rcx]; inc rcx; mov [rsi], rcx; ret
+        code.emit(&[0xBA]);
+        code.emit_u32(INPUT_BUFFER);
+        code.emit(&[0x0F, 0xB6, 0x04, 0x0A, 0x48, 0xFF, 0xC1, 0x48, 0x89, 0x0E, 0xC3]);
+        code.bind(ended);
+        // mov rax, -1; ret
+        code.emit(&[0x48, 0xC7, 0xC0, 0xFF, 0xFF, 0xFF, 0xFF, 0xC3]);
+    }
+
+    // fix_index, called with an index outside the tape in rdi, following the bounds policy
+    fn emit_bounds_routine(&mut self) {
+        let cells_count = self.tape_bounds.cells_count() as i32;
+        let code = &mut self.code;
+        code.bind(self.fix_index);
+
+        match self.bounds_policy {
+            BoundsPolicy::Clamp => {
+                let below = code.new_label();
+                // test rdi, rdi; js below; mov rdi, last_cell; ret
+                code.emit(&[0x48, 0x85, 0xFF]);
+                code.emit_jump(&[0x0F, 0x88], below);
+                code.emit(&[0x48, 0xC7, 0xC7]);
+                code.emit_i32(cells_count - 1);
+                code.emit(&[0xC3]);
+                code.bind(below);
+                // xor edi, edi; ret
+                code.emit(&[0x31, 0xFF, 0xC3]);
+            },
+            BoundsPolicy::Wrap => {
+                let positive = code.new_label();
+                // mov rax, rdi; cqo; mov rcx, cells_count
+                code.emit(&[0x48, 0x89, 0xF8, 0x48, 0x99, 0x48, 0xC7, 0xC1]);
+                code.emit_i32(cells_count);
+                // idiv rcx; mov rdi, rdx; test rdi, rdi; jns positive; add rdi, rcx
+                code.emit(&[0x48, 0xF7, 0xF9, 0x48, 0x89, 0xD7, 0x48, 0x85, 0xFF]);
+                code.emit_jump(&[0x0F, 0x89], positive);
+                code.emit(&[0x48, 0x01, 0xCF]);
+                code.bind(positive);
+                code.emit(&[0xC3]);
+            },
+            BoundsPolicy::Abort => self.emit_abort_routine(),
+        }
+    }
+
+    // stops the program as the index in rdi is outside the tape, with the line and
+    // column of the instruction in r8 and r9, writing the same message as the interpreter
+    fn emit_abort_routine(&mut self) {
+        let (before_tape, past_tape) = ((self.tape_bounds.lowest - 1) as i32, (self.tape_bounds.highest + 1) as i32);
+        let code = &mut self.code;
+        let (write_number, write_error, past_end) = (code.new_label(), code.new_label(), code.new_label());
+        let messages = [code.new_label(), code.new_label(), code.new_label(), code.new_label()];
+
+        // a run of moves goes one way, so the head left the tape right past its edge
+        // mov r14, past_tape; test rdi, rdi; jns past_end; mov r14, before_tape
+        code.emit(&[0x49, 0xC7, 0xC6]);
+        code.emit_i32(past_tape);
+        code.emit(&[0x48, 0x85, 0xFF]);
+        code.emit_jump(&[0x0F, 0x89], past_end);
+        code.emit(&[0x49, 0xC7, 0xC6]);
+        code.emit_i32(before_tape);
+        code.bind(past_end);
+        code.emit_jump(&[0xE8], self.flush_output);
+
+        let numbers: [&[u8]; 3] = [
+            // mov rax, r14
+            &[0x4C, 0x89, 0xF0],
+            // mov rax, r8
+            &[0x4C, 0x89, 0xC0],
+            // mov rax, r9
+            &[0x4C, 0x89, 0xC8],
+        ];
+
+        for (index, message) in [HEAD_MESSAGE, LINE_MESSAGE, COLUMN_MESSAGE, END_MESSAGE].iter().enumerate() {
+            // mov esi, message; mov edx, length; call write_error
+            code.emit(&[0xBE]);
+            code.emit_address(messages[index]);
+            code.emit(&[0xBA]);
+            code.emit_u32(message.len() as u32);
+            code.emit_jump(&[0xE8], write_error);
+
+            if let Some(number) = numbers.get(index) {
+                code.emit(number);
+                code.emit_jump(&[0xE8], write_number);
+            }
+        }
+
+        // mov eax, 60; mov edi, 4; syscall
+        code.emit(&[0xB8, 60, 0, 0, 0, 0xBF, 4, 0, 0, 0, 0x0F, 0x05]);
+
+        // write_number writes the number in rax to stderr
+        let (positive, digits, unsigned) = (code.new_label(), code.new_label(), code.new_label());
+        code.bind(write_number);
+        // mov esi, number_buffer + 24; mov r10, rax; test rax, rax; jns positive; neg rax
+        code.emit(&[0xBE]);
+        code.emit_u32(NUMBER_BUFFER + 24);
+        code.emit(&[0x49, 0x89, 0xC2, 0x48, 0x85, 0xC0]);
+        code.emit_jump(&[0x0F, 0x89], positive);
+        code.emit(&[0x48, 0xF7, 0xD8]);
+        code.bind(positive);
+        // mov ecx, 10
+        code.emit(&[0xB9, 10, 0, 0, 0]);
+        code.bind(digits);
+        // xor edx, edx; div rcx; add dl, '0'; dec rsi; mov [rsi], dl; test rax, rax; jnz digits
+        code.emit(&[0x31, 0xD2, 0x48, 0xF7, 0xF1, 0x80, 0xC2, b'0', 0x48, 0xFF, 0xCE, 0x88, 0x16, 0x48, 0x85, 0xC0]);
+        code.emit_jump(&[0x0F, 0x85], digits);
+        // test r10, r10; jns unsigned; dec rsi; mov byte [rsi], '-'
+        code.emit(&[0x4D, 0x85, 0xD2]);
+        code.emit_jump(&[0x0F, 0x89], unsigned);
+        code.emit(&[0x48, 0xFF, 0xCE, 0xC6, 0x06, b'-']);
+        code.bind(unsigned);
+        // mov edx, number_buffer + 24; sub rdx, rsi
+        code.emit(&[0xBA]);
+        code.emit_u32(NUMBER_BUFFER + 24);
+        code.emit(&[0x48, 0x29, 0xF2]);
+
+        // write_error writes rdx bytes from rsi to stderr
+        code.bind(write_error);
+        // mov edi, 2; mov eax, 1; syscall; ret
+        code.emit(&[0xBF, 2, 0, 0, 0, 0xB8, 1, 0, 0, 0, 0x0F, 0x05, 0xC3]);
+
+        for (label, message) in messages.into_iter().zip([HEAD_MESSAGE, LINE_MESSAGE, COLUMN_MESSAGE, END_MESSAGE]) {
+            code.bind(label);
+            code.emit(message);
+        }
+    }
+
+    // the whole executable, headers first
+    fn executable(&mut self, ops: &[Op]) -> Vec<u8> {
+        self.code = MachineCode::default();
+        self.put_byte = self.code.new_label();
+        self.get_byte = self.code.new_label();
+        self.flush_output = self.code.new_label();
+        self.fix_index = self.code.new_label();
+
+        // mov ebx, tape; mov r12, head_start
+        self.code.emit(&[0xBB]);
+        self.code.emit_u32(TAPE);
+        self.code.emit(&[0x49, 0xC7, 0xC4]);
+        self.code.emit_i32(get_head_start(self.tape_bounds) as i32);
+
+        self.translate_ops(ops);
+
+        // call flush_output; mov eax, 60; xor edi, edi; syscall
+        self.code.emit_jump(&[0xE8], self.flush_output);
+        self.code.emit(&[0xB8, 60, 0, 0, 0, 0x31, 0xFF, 0x0F, 0x05]);
+
+        self.emit_io_routines();
+        self.emit_bounds_routine();
+
+        let code = std::mem::take(&mut self.code).finish(CODE_ADDRESS + HEADERS_SIZE as u32);
+        let tape_size = self.tape_bounds.cells_count() * get_cell_size(self.cell_width).1;
+
+        let mut executable = get_headers(code.len(), (TAPE - DATA_ADDRESS) as u64 + tape_size as u64);
+        executable.extend(code);
+
+        executable
+    }
+
+    pub fn translate<'a>(&mut self, command: &str) -> Result<(), TranslateError<'a>> {
+        let program = match Program::parse(command) {
+            Ok(program) => program,
+            Err(error) => return Err(TranslateError::Syntax(error)),
+        };
+
+        // translated cells always wrap
+        let program = optimize(program, self.optimization, self.cell_width, CellOverflow::Wrap, self.bounds_policy);
+        check_immediates(self.tape_bounds, &program.ops)?;
+        self.source = program.source.clone();
+
+        let executable = self.executable(&program.ops);
+
+        let mut file = match create_executable(&self.file_name) {
+            Ok(file) => file,
+            Err(_) => return Err(TranslateError::Write("Invalid file name.")),
+        };
+
+        match file.write_all(&executable) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(TranslateError::Write("Couldn't write to file.")),
+        }
+    }
+}
+
+#[test]
+fn elf_headers() {
+    let headers = get_headers(100, 30048);
+
+    assert_eq!(headers.len(), HEADERS_SIZE);
+    assert_eq!(headers[..4], *b"\x7fELF");
+    // the entry point is right after the headers
+    assert_eq!(headers[24..32], 0x4000B0u64.to_le_bytes());
+
+    // the code segment spans the whole file, the data segment takes none of it
+    let code = &headers[ELF_HEADER_SIZE..ELF_HEADER_SIZE + PROGRAM_HEADER_SIZE];
+    assert_eq!(code[32..40], 276u64.to_le_bytes());
+    let data = &headers[ELF_HEADER_SIZE + PROGRAM_HEADER_SIZE..];
+    assert_eq!(data[32..40], 0u64.to_le_bytes());
+    assert_eq!(data[40..48], 30048u64.to_le_bytes());
+}
+
+// writes the program as an executable and runs it on the input
+#[cfg(all(test, target_os = "linux", target_arch = "x86_64"))]
+fn write_and_run(command: &str, config: &Config, input: &[u8]) -> crate::native::NativeRun {
+    use std::process::{Command, Stdio};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // the tests run at the same time, each writes executables of its own
+    static RUN_COUNT: AtomicUsize = AtomicUsize::new(0);
+    let binary = std::env::temp_dir().join(String::from("bf_elfwriter_") + &std::process::id().to_string() + "_" +
+                                           &RUN_COUNT.fetch_add(1, Ordering::Relaxed).to_string());
+
+    let mut writer = ElfWriter::new(binary.to_str().unwrap(), config).unwrap();
+    assert!(writer.translate(command).is_ok());
+    drop(writer);
+
+    let mut child = Command::new(&binary).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+    // a program that ends without reading all of its input closes the pipe early
+    let _ = child.stdin.take().unwrap().write_all(input);
+    let output = child.wait_with_output().unwrap();
+    std::fs::remove_file(binary).unwrap();
+
+    crate::native::NativeRun { output: output.stdout, errors: output.stderr, exit_code: output.status.code() }
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+#[test]
+fn run_shared_settings() {
+    use crate::native::differential_tests::*;
+
+    for (corpus, config) in settings() {
+        compare(corpus, &config, &write_and_run);
+    }
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+#[test]
+fn run_clamped_multiply() {
+    use crate::native::differential_tests::*;
+
+    let config = Config::default();
+    assert_eq!(write_and_run("+[-<+>]<.", &config, b""), interpret("+[-<+>]<.", &config, b""));
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+#[test]
+fn run_with_long_input() {
+    use crate::native::differential_tests::*;
+
+    // the input is longer than the buffer it is read into
+    let input: Vec<u8> = (0..10000).map(|n| (n % 255 + 1) as u8).chain([0]).collect();
+
+    for optimization in [OptimizationLevel::None, OptimizationLevel::Idioms] {
+        let config = Config { optimization, ..Config::default() };
+        assert_eq!(write_and_run(",[.,]", &config, &input), interpret(",[.,]", &config, &input));
+    }
+}
+
+#[test]
+fn tape_too_large() {
+    let binary = std::env::temp_dir().join("bf_elfwriter_tape_too_large");
+    let config = Config { tape_bounds: TapeBounds::bidirectional(3_000_000_000), bounds_policy: BoundsPolicy::Abort, ..Config::default() };
+
+    let mut writer = ElfWriter::new(binary.to_str().unwrap(), &config).unwrap();
+    assert!(matches!(writer.translate("+[>+]"), Err(TranslateError::Unsupported(_))));
+    assert!(!binary.exists());
+}
@@ -0,0 +1,115 @@
+use std::collections::VecDeque;
+
+// what one executed instruction or debugger edit changed, enough to undo it
+pub struct StepRecord {
+    pub instruction_pos: usize,
+    pub head_position: isize,
+    // cells written in order, with the values they had before
+    pub writes: Vec<(isize, i64)>,
+    // byte taken from the input
+    pub read: Option<u8>,
+    pub wrote_output: bool,
+    // false for edits made in the debugger, which are not counted as steps
+    pub executed: bool,
+}
+
+// entries a record takes, one for the step and one for each cell it wrote,
+// so a multiply loop with many targets counts for more than a move
+fn entries(record: &StepRecord) -> usize {
+    1 + record.writes.len()
+}
+
+// the last steps executed, the oldest are forgotten once they take more than max_entries entries,
+// which bounds the memory used whatever the steps wrote
+pub struct History {
+    steps: VecDeque<StepRecord>,
+    entries: usize,
+    max_entries: usize,
+}
+
+impl History {
+    pub fn new(max_entries: usize) -> History {
+        History {
+            steps: VecDeque::new(),
+            entries: 0,
+            max_entries,
+        }
+    }
+
+    pub fn push(&mut self, record: StepRecord) {
+        // a step that doesn't fit can't be undone, and neither can any step before it
+        if entries(&record) > self.max_entries {
+            self.steps.clear();
+            self.entries = 0;
+            return;
+        }
+
+        self.entries += entries(&record);
+        self.steps.push_back(record);
+
+        while self.entries > self.max_entries {
+            let oldest = self.steps.pop_front().expect("the entries belong to remembered steps");
+            self.entries -= entries(&oldest);
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<StepRecord> {
+        let record = self.steps.pop_back()?;
+        self.entries -= entries(&record);
+
+        Some(record)
+    }
+
+    /// remembered steps from the oldest to the last
+    pub fn iter(&self) -> std::collections::vec_deque::Iter<'_, StepRecord> {
+        self.steps.iter()
+    }
+}
+
+#[cfg(test)]
+mod history_tests {
+    use crate::history::*;
+
+    fn record(instruction_pos: usize) -> StepRecord {
+        StepRecord {
+            instruction_pos,
+            head_position: 0,
+            writes: Vec::new(),
+            read: None,
+            wrote_output: false,
+            executed: true,
+        }
+    }
+
+    #[test]
+    fn forget_oldest() {
+        let mut history = History::new(2);
+
+        history.push(record(1));
+        history.push(record(2));
+        history.push(record(3));
+
+        assert_eq!(history.pop().unwrap().instruction_pos, 3);
+        assert_eq!(history.pop().unwrap().instruction_pos, 2);
+        assert!(history.pop().is_none());
+    }
+
+    #[test]
+    fn count_writes() {
+        let mut history = History::new(4);
+
+        history.push(record(1));
+        history.push(record(2));
+        history.push(StepRecord { writes: vec![(0, 0), (1, 0)], ..record(3) });
+
+        // the last step takes three entries
+        assert_eq!(history.pop().unwrap().instruction_pos, 3);
+        assert_eq!(history.pop().unwrap().instruction_pos, 2);
+        assert!(history.pop().is_none());
+
+        // a step larger than the whole history leaves nothing to undo
+        history.push(record(4));
+        history.push(StepRecord { writes: vec![(0, 0); 4], ..record(5) });
+        assert!(history.pop().is_none());
+    }
+}
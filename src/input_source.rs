@@ -0,0 +1,188 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+
+/// what , stores once the input has ended
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EofBehavior {
+    Zero,
+    /// 255 for byte cells, -1 as seen by the program
+    AllOnes,
+    Unchanged,
+}
+
+/// where the bytes for , come from
+pub enum InputSource {
+    Stdin,
+    File(BufReader<File>),
+    Buffer(VecDeque<u8>),
+    Reader(BufReader<Box<dyn Read>>),
+    /// prompt for every , and read a decimal number or a single character
+    InteractiveDecimal,
+}
+
+fn read_byte(reader: &mut dyn Read) -> Option<u8> {
+    let mut byte = [0u8];
+
+    loop {
+        match reader.read(&mut byte) {
+            Ok(0) => return None,
+            Ok(_) => return Some(byte[0]),
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            Err(_) => return None,
+        }
+    }
+}
+
+fn prompt_byte() -> Option<u8> {
+    println!();
+    print!("Input: ");
+    // flush to avoid delay in print
+    io::stdout().flush().unwrap();
+
+    let mut input = String::new();
+
+    let read = std::io::stdin()
+        .read_line(&mut input)
+        .expect("Failed to get input!");
+
+    if read == 0 || !input.is_ascii() {
+        return None;
+    }
+
+    //try to get as a digit directly
+    match input.trim().parse::<u8>() {
+        Ok(digit) => Some(digit),
+        Err(_) => Some(input.as_bytes()[0]),
+    }
+}
+
+impl InputSource {
+    pub fn from_file<'a>(file_name: &str) -> Result<InputSource, &'a str> {
+        match File::open(file_name) {
+            Ok(file) => Ok(InputSource::File(BufReader::new(file))),
+            Err(_) => Err("Couldn't open input file."),
+        }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> InputSource {
+        InputSource::Buffer(bytes.iter().copied().collect())
+    }
+
+    pub fn from_reader(reader: impl Read + 'static) -> InputSource {
+        let reader: Box<dyn Read> = Box::new(reader);
+        InputSource::Reader(BufReader::new(reader))
+    }
+
+    /// Reads the rest of a file or reader into memory and returns the bytes left,
+    /// None for stdin and prompts, which can't be read ahead.
+    pub fn buffer_remaining(&mut self) -> Option<Vec<u8>> {
+        let reader: &mut dyn Read = match self {
+            InputSource::Stdin | InputSource::InteractiveDecimal => return None,
+            InputSource::Buffer(bytes) => return Some(bytes.iter().copied().collect()),
+            InputSource::File(reader) => reader,
+            InputSource::Reader(reader) => reader,
+        };
+
+        let mut bytes = Vec::new();
+        while let Some(byte) = read_byte(reader) {
+            bytes.push(byte);
+        }
+
+        *self = InputSource::from_bytes(&bytes);
+
+        Some(bytes)
+    }
+
+    /// None when the input has ended
+    pub fn next_byte(&mut self) -> Option<u8> {
+        match self {
+            InputSource::Stdin => read_byte(&mut io::stdin().lock()),
+            InputSource::File(reader) => read_byte(reader),
+            InputSource::Buffer(bytes) => bytes.pop_front(),
+            InputSource::Reader(reader) => read_byte(reader),
+            InputSource::InteractiveDecimal => prompt_byte(),
+        }
+    }
+}
+
+impl EofBehavior {
+    pub fn from_name(name: &str) -> Option<EofBehavior> {
+        match name {
+            "0" | "zero" => Some(EofBehavior::Zero),
+            "255" | "-1" => Some(EofBehavior::AllOnes),
+            "unchanged" => Some(EofBehavior::Unchanged),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            EofBehavior::Zero => "zero",
+            EofBehavior::AllOnes => "-1",
+            EofBehavior::Unchanged => "unchanged",
+        }
+    }
+}
+
+#[cfg(test)]
+mod input_source_tests {
+    use crate::input_source::*;
+
+    #[test]
+    fn buffer_input() {
+        let mut input = InputSource::from_bytes(b"ab\xff");
+
+        assert_eq!(input.next_byte(), Some(b'a'));
+        assert_eq!(input.next_byte(), Some(b'b'));
+        assert_eq!(input.next_byte(), Some(255));
+        assert_eq!(input.next_byte(), None);
+    }
+
+    #[test]
+    fn reader_input() {
+        let mut input = InputSource::from_reader(&b"hi"[..]);
+
+        assert_eq!(input.next_byte(), Some(b'h'));
+        assert_eq!(input.next_byte(), Some(b'i'));
+        assert_eq!(input.next_byte(), None);
+    }
+
+    #[test]
+    fn file_input() {
+        let file_name = std::env::temp_dir().join("bf_input_source_test.txt");
+        std::fs::write(&file_name, "xy").unwrap();
+
+        let mut input = InputSource::from_file(file_name.to_str().unwrap()).unwrap();
+
+        assert_eq!(input.next_byte(), Some(b'x'));
+        assert_eq!(input.next_byte(), Some(b'y'));
+        assert_eq!(input.next_byte(), None);
+
+        std::fs::remove_file(file_name).unwrap();
+    }
+
+    #[test]
+    fn buffer_remaining() {
+        let mut input = InputSource::from_reader(&b"abc"[..]);
+        input.next_byte();
+
+        assert_eq!(input.buffer_remaining(), Some(b"bc".to_vec()));
+        assert_eq!(input.next_byte(), Some(b'b'));
+        assert_eq!(input.buffer_remaining(), Some(b"c".to_vec()));
+        assert_eq!(InputSource::Stdin.buffer_remaining(), None);
+    }
+
+    #[test]
+    fn eof_names() {
+        assert_eq!(EofBehavior::from_name("zero"), Some(EofBehavior::Zero));
+        assert_eq!(EofBehavior::from_name("-1"), Some(EofBehavior::AllOnes));
+        assert_eq!(EofBehavior::from_name("unchanged"), Some(EofBehavior::Unchanged));
+        assert_eq!(EofBehavior::from_name("eof"), None);
+    }
+
+    #[test]
+    fn missing_file() {
+        assert!(InputSource::from_file("/nonexistent/input.txt").is_err());
+    }
+}
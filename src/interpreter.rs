@@ -1,50 +1,255 @@
+use crate::bracket_validator::*;
+use crate::config::*;
+use crate::history::*;
+use crate::input_source::*;
 use crate::memory_tape::*;
+use crate::optimizer::*;
+use crate::profiler::*;
+use crate::program::*;
+use crate::snapshot::*;
 
+use std::fmt;
 use std::io::{self, Write};
-use std::{thread, time::Duration};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// the clock is only read every so many steps to keep it cheap
+const STEPS_PER_TIME_CHECK: u64 = 1024;
 
 pub fn clear_terminal() {
     // try to clear windows terminal
-    match std::process::Command::new("cmd")
-            .args(&["/c", "cls"])
+    let cleared = match std::process::Command::new("cmd")
+            .args(["/c", "cls"])
             .spawn()
-            .expect("cls command failed to start")
-            .wait() 
             {
-                Ok(_) => (),
-                // if wait failed try to clear linux terminal
-                Err(_) => {
-                            std::process::Command::new("clear")
-                                .spawn()
-                                .expect("clear command failed to start")
-                                .wait()
-                                .expect("faild to wait");
-                          } 
+                Ok(mut child) => child.wait().is_ok(),
+                Err(_) => false,
             };
+
+    // if that failed try to clear linux terminal
+    if !cleared {
+        if let Ok(mut child) = std::process::Command::new("clear").spawn() {
+            child.wait().expect("faild to wait");
+        }
+    }
+}
+
+// program ops flattened so that loops become jumps to their matching bracket
+#[derive(Debug, PartialEq)]
+enum Instruction {
+    Add(i32),
+    Move(isize),
+    In,
+    Out,
+    // jump past the matching ] when the current cell is zero
+    JumpIfZero(usize),
+    // jump back past the matching [ when the current cell is not zero
+    JumpIfNotZero(usize),
+    Clear,
+    Scan(isize),
+    MulAdd(Vec<(isize, i32)>),
+}
+
+/// What stopped a run before its end.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RuntimeErrorKind {
+    Tape(TapeError),
+    /// the output couldn't be written, for example to a closed pipe
+    Output(io::ErrorKind),
+    /// the JSON trace couldn't be written
+    Trace(io::ErrorKind),
+}
+
+/// A run stopped by the tape, for example by a cell overflow, or by its output.
+#[derive(Debug)]
+pub struct RuntimeError {
+    pub error: RuntimeErrorKind,
+    /// position of the failing instruction in the command
+    pub position: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Why a command couldn't be interpreted.
+#[derive(Debug)]
+pub enum InterpretError {
+    Syntax(BracketError),
+    Runtime(RuntimeError),
+}
+
+impl fmt::Display for RuntimeErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuntimeErrorKind::Tape(error) => write!(f, "{}", error),
+            RuntimeErrorKind::Output(_) => write!(f, "Couldn't write output"),
+            RuntimeErrorKind::Trace(_) => write!(f, "Couldn't write trace"),
+        }
+    }
+}
+
+impl From<TapeError> for RuntimeErrorKind {
+    fn from(error: TapeError) -> RuntimeErrorKind {
+        RuntimeErrorKind::Tape(error)
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at line {}, column {}.", self.error, self.line, self.column)
+    }
+}
+
+impl fmt::Display for InterpretError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InterpretError::Syntax(error) => write!(f, "{}", error),
+            InterpretError::Runtime(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl From<BracketError> for InterpretError {
+    fn from(error: BracketError) -> InterpretError {
+        InterpretError::Syntax(error)
+    }
 }
 
+impl From<RuntimeError> for InterpretError {
+    fn from(error: RuntimeError) -> InterpretError {
+        InterpretError::Runtime(error)
+    }
+}
+
+impl Instruction {
+    // name and argument used in the JSON trace
+    fn opcode(&self) -> (&str, Option<i64>) {
+        match self {
+            Instruction::Add(amount) => ("add", Some(*amount as i64)),
+            Instruction::Move(distance) => ("move", Some(*distance as i64)),
+            Instruction::In => ("in", None),
+            Instruction::Out => ("out", None),
+            Instruction::JumpIfZero(_) => ("jump_if_zero", None),
+            Instruction::JumpIfNotZero(_) => ("jump_if_not_zero", None),
+            Instruction::Clear => ("clear", None),
+            Instruction::Scan(distance) => ("scan", Some(*distance as i64)),
+            Instruction::MulAdd(_) => ("mul_add", None),
+        }
+    }
+}
+
+fn escape_json(c: char) -> String {
+    match c {
+        '"' => String::from("\\\""),
+        '\\' => String::from("\\\\"),
+        _ if c.is_control() => format!("\\u{:04x}", c as u32),
+        _ => c.to_string(),
+    }
+}
+
+// state before an instruction, completed into a trace record once it ran
+struct TraceStart {
+    head_position: isize,
+    value: i64,
+    input_bytes: u64,
+    output_bytes: u64,
+}
+
+// executions and taken jumps of every instruction, kept when profiling
+struct InstructionCounts {
+    executed: Vec<u64>,
+    jumped: Vec<u64>,
+}
+
+/// How a run ended.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RunStatus {
+    /// the program reached its end
+    Finished,
+    StepLimitExceeded,
+    TimeLimitExceeded,
+}
+
+impl fmt::Display for RunStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RunStatus::Finished => write!(f, "Finished"),
+            RunStatus::StepLimitExceeded => write!(f, "Step limit exceeded"),
+            RunStatus::TimeLimitExceeded => write!(f, "Time limit exceeded"),
+        }
+    }
+}
+
+/// Counters collected while a program runs.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Statistics {
+    /// Instructions executed, a collapsed run or an optimized loop counts as one,
+    /// a scan counts once for every move.
+    pub steps: u64,
+    pub input_bytes: u64,
+    pub output_bytes: u64,
+}
+
+/// State of the interpreter after a run.
+#[derive(Clone, Debug)]
+pub struct RunResult {
+    /// Every byte written by `.`.
+    pub output: Vec<u8>,
+    /// Cells the head reached, the first one has index `tape_start`.
+    pub tape: Vec<i64>,
+    pub tape_start: isize,
+    pub head_position: isize,
+    pub statistics: Statistics,
+    pub status: RunStatus,
+    /// Position of the next instruction in the command, its length once the program finished.
+    pub position: usize,
+}
+
+/// Runs a loaded program, optionally animating the tape after every step.
 pub struct Interpreter {
     tape: MemoryTape,
     should_print_tape: bool,
+    config: Config,
+    input: InputSource,
+    // bytes given back to the input by step_back, read before the input source
+    replayed_input: Vec<u8>,
+    statistics: Statistics,
+    history: Option<History>,
+    counts: Option<InstructionCounts>,
+    // one JSON record per executed instruction is written here when tracing
+    trace: Option<Box<dyn Write>>,
 
-    opened_brackets: Vec<usize>,
     command: Vec<char>,
-    command_pos: usize,
+    instructions: Vec<Instruction>,
+    spans: Vec<Span>,
+    instruction_pos: usize,
+    status: RunStatus,
 
-    ouput: String,
+    output: Box<dyn Write>,
+    // every byte written so far
+    output_bytes: Vec<u8>,
 }
 
 impl Interpreter {
-    pub fn new(should_print_tape: bool) -> Interpreter {
+    pub fn new(should_print_tape: bool, config: &Config, input: InputSource, output: Box<dyn Write>) -> Interpreter {
         Interpreter {
-            tape: MemoryTape::new(),
-            should_print_tape: should_print_tape,
+            tape: MemoryTape::with_bounds(config.cell_width, config.cell_overflow, config.tape_bounds, config.bounds_policy),
+            should_print_tape,
+            config: config.clone(),
+            input,
+            replayed_input: Vec::new(),
+            statistics: Statistics::default(),
+            history: None,
+            counts: None,
+            trace: None,
 
-            opened_brackets: Vec::new(),
             command: Vec::new(),
-            command_pos: 0,
+            instructions: Vec::new(),
+            spans: Vec::new(),
+            instruction_pos: 0,
+            status: RunStatus::Finished,
 
-            ouput: String::new(),   
+            output,
+            output_bytes: Vec::new(),
         }
     }
 
@@ -60,67 +265,89 @@ impl Interpreter {
         }
     }
 
-    fn move_to_next_rbracket(&mut self) {
-        loop {
-            if self.command_pos >= self.command.len()
-            {
-                return;
-            }
+    fn flatten(&mut self, ops: &[Op]) {
+        for op in ops {
+            match &op.kind {
+                OpKind::Add(amount) => self.push_instruction(Instruction::Add(*amount), op.span),
+                OpKind::Move(distance) => self.push_instruction(Instruction::Move(*distance), op.span),
+                OpKind::In => self.push_instruction(Instruction::In, op.span),
+                OpKind::Out => self.push_instruction(Instruction::Out, op.span),
+                OpKind::Clear => self.push_instruction(Instruction::Clear, op.span),
+                OpKind::Scan(distance) => self.push_instruction(Instruction::Scan(*distance), op.span),
+                OpKind::MulAdd(targets) => self.push_instruction(Instruction::MulAdd(targets.clone()), op.span),
+                OpKind::Loop(body) => {
+                    let open_pos = self.instructions.len();
+                    let open_span = Span { start: op.span.start, end: op.span.start + 1 };
+                    let close_span = Span { start: op.span.end - 1, end: op.span.end };
 
-            let ch = self.command[self.command_pos];
-            if ch == ']' {
-                return;
-            }
+                    // target is patched once the matching ] is known
+                    self.push_instruction(Instruction::JumpIfZero(0), open_span);
+                    self.flatten(body);
 
-            self.command_pos += 1;
+                    let close_pos = self.instructions.len();
+                    self.push_instruction(Instruction::JumpIfNotZero(open_pos + 1), close_span);
+                    self.instructions[open_pos] = Instruction::JumpIfZero(close_pos + 1);
+                },
+            }
         }
     }
 
-    fn act_on_lbracket(&mut self) {
-        if self.tape.get_current_value() == 0  {
-            // move to next command
-            self.opened_brackets.pop();
-            self.move_to_next_rbracket();
+    fn push_instruction(&mut self, instruction: Instruction, span: Span) {
+        self.instructions.push(instruction);
+        self.spans.push(span);
+    }
 
-            return;
+    fn get_input(&mut self) -> Result<(), RuntimeErrorKind> {
+        // a prompt for input must not hide output that is still buffered
+        self.write_output()?;
+
+        let byte = match self.replayed_input.pop() {
+            Some(byte) => Some(byte),
+            None => self.input.next_byte(),
+        };
+
+        match (byte, self.config.eof) {
+            (Some(byte), _) => {
+                self.tape.set_cell_value(byte as i64);
+                self.statistics.input_bytes += 1;
+            },
+            (None, EofBehavior::Zero) => self.tape.set_cell_value(0),
+            (None, EofBehavior::AllOnes) => self.tape.set_cell_value(self.tape.width().all_ones()),
+            (None, EofBehavior::Unchanged) => (),
         }
 
-        self.opened_brackets.push(self.command_pos);
+        Ok(())
     }
 
-    fn act_on_rbracket(&mut self) {
-        if self.tape.get_current_value() == 0 {
-            // move to next command
-            self.opened_brackets.pop();
+    fn put_output(&mut self) -> Result<(), RuntimeErrorKind> {
+        // wider cells write their lowest byte
+        let byte = self.tape.get_current_value() as u8;
 
-            return;
+        self.output.write_all(&[byte]).map_err(|error| RuntimeErrorKind::Output(error.kind()))?;
+        self.output_bytes.push(byte);
+        self.statistics.output_bytes += 1;
+
+        if byte == b'\n' {
+            self.write_output()?;
         }
 
-        self.command_pos = *self.opened_brackets.last().unwrap();
+        Ok(())
     }
 
-    fn get_input(&mut self) {
-        println!();
-        print!("Input: ");
-        // flush to avoid delay in print
-        io::stdout().flush().unwrap();
-
-        let mut input = String::new();
+    fn write_output(&mut self) -> Result<(), RuntimeErrorKind> {
+        self.output.flush().map_err(|error| RuntimeErrorKind::Output(error.kind()))?;
 
-        std::io::stdin()
-            .read_line(&mut input)
-            .expect("Failed to get input!");
-
-        if !input.is_ascii() {
-            return;
+        // the trace is kept in step with the output
+        if let Some(trace) = &mut self.trace {
+            trace.flush().map_err(|error| RuntimeErrorKind::Trace(error.kind()))?;
         }
 
-        //try to get as a digit directly
-        let result = input.trim().parse::<u8>();
-        match result {
-            Ok(digit) => self.tape.set_cell_value(digit),
-            Err(_) =>    self.tape.set_cell_value(input.as_bytes()[0]),
-        }
+        Ok(())
+    }
+
+    /// Writes the output buffered so far, fails at the next instruction when it can't be written.
+    pub fn flush_output(&mut self) -> Result<(), RuntimeError> {
+        self.write_output().map_err(|error| self.runtime_error(error))
     }
 
     fn print_command(&self, print_with_pos: bool) {
@@ -128,10 +355,11 @@ impl Interpreter {
             return;
         }
 
+        let span = self.spans[self.instruction_pos];
         let current_command = if print_with_pos {
-                                String::from_iter(&self.command[0..self.command_pos])   +
-                                "{" + &String::from(self.command[self.command_pos]) + "}" +
-                                &String::from_iter(&self.command[self.command_pos+1..])
+                                String::from_iter(&self.command[0..span.start])   +
+                                "{" + &String::from_iter(&self.command[span.start..span.end]) + "}" +
+                                &String::from_iter(&self.command[span.end..])
                               }else {
                                 String::from_iter(&self.command)
                               };
@@ -146,58 +374,1270 @@ impl Interpreter {
         }
 
         self.tape.print_tape_sniplet();
-        if !self.ouput.is_empty() {
-            println!("Output: {}", self.ouput);
+        if !self.output_bytes.is_empty() {
+            println!("Output: {}", String::from_utf8_lossy(&self.output_bytes));
+        }
+    }
+
+    /// Prepares a parsed program to be run from its beginning.
+    pub fn load(&mut self, program: &Program) {
+        let program = optimize(program.clone(), self.config.optimization, self.config.cell_width, self.config.cell_overflow,
+                           self.config.bounds_policy);
+
+        self.command = program.source.clone();
+        self.instructions = Vec::new();
+        self.spans = Vec::new();
+        self.flatten(&program.ops);
+        self.instruction_pos = 0;
+
+        if self.counts.is_some() {
+            self.enable_profiling();
+        }
+    }
+
+    /// Counts the executions of every instruction and loop, see `profile`.
+    pub fn enable_profiling(&mut self) {
+        self.counts = Some(InstructionCounts {
+            executed: vec![0; self.instructions.len()],
+            jumped: vec![0; self.instructions.len()],
+        });
+    }
+
+    /// Counts collected since profiling was enabled, None when it wasn't.
+    pub fn profile(&self) -> Option<Profile> {
+        let counts = self.counts.as_ref()?;
+
+        // every position of a collapsed run or an optimized loop counts as executed
+        let mut position_counts = vec![0; self.command.len()];
+        for (span, executed) in self.spans.iter().zip(&counts.executed) {
+            for count in &mut position_counts[span.start..span.end] {
+                *count += executed;
+            }
+        }
+
+        let mut loops = Vec::new();
+        for (pos, instruction) in self.instructions.iter().enumerate() {
+            if let Instruction::JumpIfZero(target) = instruction {
+                let close_pos = target - 1;
+
+                loops.push(LoopProfile {
+                    start: self.spans[pos].start,
+                    end: self.spans[close_pos].end,
+                    entries: counts.executed[pos],
+                    // the body starts when [ doesn't jump past the loop and when ] jumps back
+                    iterations: counts.executed[pos] - counts.jumped[pos] + counts.jumped[close_pos],
+                    steps: counts.executed[pos..*target].iter().sum(),
+                });
+            }
+        }
+
+        Some(Profile::new(self.command.clone(), position_counts, loops, self.statistics.steps))
+    }
+
+    // executes the current instruction and returns the position of the next one
+    fn execute_instruction(&mut self) -> Result<usize, RuntimeErrorKind> {
+        let mut next_pos = self.instruction_pos + 1;
+
+        match &self.instructions[self.instruction_pos] {
+            Instruction::Add(amount) => self.tape.add(*amount as i64)?,
+            Instruction::Move(distance) => self.tape.move_by(*distance)?,
+
+            Instruction::JumpIfZero(target) => {
+                if self.tape.get_current_value() == 0 {
+                    next_pos = *target;
+                }
+            },
+            Instruction::JumpIfNotZero(target) => {
+                if self.tape.get_current_value() != 0 {
+                    next_pos = *target;
+                }
+            },
+
+            Instruction::Clear => self.tape.set_cell_value(0),
+            // one move per step, so the limits are checked while scanning
+            Instruction::Scan(distance) => {
+                if self.tape.get_current_value() != 0 {
+                    self.tape.move_by(*distance)?;
+
+                    if self.tape.get_current_value() != 0 {
+                        next_pos = self.instruction_pos;
+                    }
+                }
+            },
+            Instruction::MulAdd(targets) => {
+                let value = self.tape.get_current_value() as i128;
+
+                // the loop never runs its body when the counter is zero, so no target is reached
+                if value != 0 {
+                    for (offset, factor) in targets {
+                        // only unbounded cells can hold a product that doesn't fit
+                        let amount = i64::try_from(value * *factor as i128)
+                            .map_err(|_| TapeError::CellOverflow { index: self.tape.head_position })?;
+
+                        self.tape.add_at(*offset, amount)?;
+                    }
+                    self.tape.set_cell_value(0);
+                }
+            },
+
+            Instruction::In => self.get_input()?,
+            Instruction::Out => self.put_output()?,
+        };
+
+        Ok(next_pos)
+    }
+
+    fn runtime_error(&self, error: RuntimeErrorKind) -> RuntimeError {
+        let (line, column) = self.current_line_and_column();
+
+        RuntimeError {
+            error,
+            position: self.current_position(),
+            line,
+            column,
+        }
+    }
+
+    // checks the limits in the config before the next step, both count from the start of the run
+    fn check_limits(&self, started: Instant, first_step: u64) -> RunStatus {
+        if let Some(step_limit) = self.config.step_limit {
+            if self.statistics.steps - first_step >= step_limit {
+                return RunStatus::StepLimitExceeded;
+            }
+        }
+
+        if let Some(time_limit) = self.config.time_limit {
+            if self.statistics.steps.is_multiple_of(STEPS_PER_TIME_CHECK) && started.elapsed() >= time_limit {
+                return RunStatus::TimeLimitExceeded;
+            }
+        }
+
+        RunStatus::Finished
+    }
+
+    // executes the current instruction and returns the position of the next one
+    fn execute_step(&mut self) -> Result<usize, RuntimeError> {
+        self.statistics.steps += 1;
+
+        let trace_start = self.trace.as_ref().map(|_| TraceStart {
+            head_position: self.tape.head_position,
+            value: self.tape.get_current_value(),
+            input_bytes: self.statistics.input_bytes,
+            output_bytes: self.statistics.output_bytes,
+        });
+
+        let result = match &self.history {
+            Some(_) => self.execute_recorded_instruction(),
+            None => self.execute_instruction(),
+        };
+
+        match result {
+            Ok(next_pos) => {
+                if let Some(trace_start) = trace_start {
+                    self.write_trace_record(trace_start, None).map_err(|error| self.runtime_error(error))?;
+                }
+
+                if let Some(counts) = &mut self.counts {
+                    counts.executed[self.instruction_pos] += 1;
+                    if next_pos != self.instruction_pos + 1 {
+                        counts.jumped[self.instruction_pos] += 1;
+                    }
+                }
+
+                Ok(next_pos)
+            },
+            Err(error) => {
+                // the error that stopped the run is reported rather than one writing the trace
+                // or the output before it
+                if let Some(trace_start) = trace_start {
+                    let _ = self.write_trace_record(trace_start, Some(&error));
+                }
+                let _ = self.write_output();
+                Err(self.runtime_error(error))
+            },
         }
     }
 
-    pub fn interpret(&mut self, command: &str) {
-        self.command = command.chars().collect();
-        
+    /// Writes a JSON Lines record for every instruction executed from now on.
+    pub fn enable_trace(&mut self, trace: Box<dyn Write>) {
+        self.trace = Some(trace);
+    }
+
+    // the record of a failed instruction also holds the error
+    fn write_trace_record(&mut self, start: TraceStart, error: Option<&RuntimeErrorKind>) -> Result<(), RuntimeErrorKind> {
+        let (opcode, operand) = self.instructions[self.instruction_pos].opcode();
+
+        // the cell the head was on, which a move leaves behind
+        let value_after = self.tape.get_value_at(start.head_position);
+
+        let read = if self.statistics.input_bytes > start.input_bytes {
+            (value_after as u8).to_string()
+        } else {
+            String::from("null")
+        };
+        let written = if self.statistics.output_bytes > start.output_bytes {
+            self.output_bytes[self.output_bytes.len() - 1].to_string()
+        } else {
+            String::from("null")
+        };
+
+        let mut record = String::from("{\"step\":") + &self.statistics.steps.to_string() +
+                         ",\"offset\":" + &self.spans[self.instruction_pos].start.to_string() +
+                         ",\"opcode\":\"" + opcode + "\"";
+        if let Some(operand) = operand {
+            record = record + ",\"operand\":" + &operand.to_string();
+        }
+        record = record + ",\"head\":" + &start.head_position.to_string() +
+                 ",\"before\":" + &start.value.to_string() +
+                 ",\"after\":" + &value_after.to_string() +
+                 ",\"read\":" + &read +
+                 ",\"written\":" + &written;
+        if let Some(error) = error {
+            let message: String = error.to_string().chars().map(escape_json).collect();
+            record = record + ",\"error\":\"" + &message + "\"";
+        }
+        record += "}\n";
+
+        if let Some(trace) = &mut self.trace {
+            trace.write_all(record.as_bytes()).map_err(|error| RuntimeErrorKind::Trace(error.kind()))?;
+        }
+
+        Ok(())
+    }
+
+    // executes the current instruction keeping what it changed in the history
+    fn execute_recorded_instruction(&mut self) -> Result<usize, RuntimeErrorKind> {
+        let head_position = self.tape.head_position;
+        let input_bytes = self.statistics.input_bytes;
+        let output_bytes = self.statistics.output_bytes;
+        self.tape.take_writes();
+
+        let result = self.execute_instruction();
+
+        // a byte read from the input is stored in the current cell
+        let read = if self.statistics.input_bytes > input_bytes {
+            Some(self.tape.get_current_value() as u8)
+        } else {
+            None
+        };
+
+        let record = StepRecord {
+            instruction_pos: self.instruction_pos,
+            head_position,
+            writes: self.tape.take_writes(),
+            read,
+            wrote_output: self.statistics.output_bytes > output_bytes,
+            executed: true,
+        };
+
+        if let Some(history) = &mut self.history {
+            history.push(record);
+        }
+
+        result
+    }
+
+    /// Remembers what the last steps changed, so they can be undone with `step_back`.
+    ///
+    /// The history holds at most max_entries entries, a step takes one plus one for each cell it wrote,
+    /// and the oldest steps are forgotten to make room.
+    pub fn enable_history(&mut self, max_entries: usize) {
+        self.tape.record_writes();
+        self.history = Some(History::new(max_entries));
+    }
+
+    /// Undoes the last step, returns false when no earlier step is remembered.
+    ///
+    /// Bytes read are given back to the input, but output already written
+    /// to the output stream stays there.
+    pub fn step_back(&mut self) -> bool {
+        let record = match self.history.as_mut().and_then(|history| history.pop()) {
+            Some(record) => record,
+            None => return false,
+        };
+
+        for (index, old_value) in record.writes.into_iter().rev() {
+            self.tape.set_value_at(index, old_value);
+        }
+        self.tape.move_to(record.head_position);
+
+        if let Some(byte) = record.read {
+            self.replayed_input.push(byte);
+            self.statistics.input_bytes -= 1;
+        }
+        if record.wrote_output {
+            self.output_bytes.pop();
+            self.statistics.output_bytes -= 1;
+        }
+
+        if record.executed {
+            self.statistics.steps -= 1;
+        }
+        self.instruction_pos = record.instruction_pos;
+
+        true
+    }
+
+    /// Sets a cell outside of the program, the change can be undone with `step_back`.
+    pub fn set_cell(&mut self, index: isize, value: i64) {
+        let old_value = self.tape.get_value_at(index);
+
+        self.tape.set_value_at(index, value);
+        self.push_edit(vec![(index, old_value)]);
+    }
+
+    /// Moves the head outside of the program, the move can be undone with `step_back`.
+    pub fn move_head(&mut self, index: isize) {
+        self.push_edit(Vec::new());
+        self.tape.move_to(index);
+    }
+
+    // remembers the head position before the edit and the old values of the cells it wrote
+    fn push_edit(&mut self, writes: Vec<(isize, i64)>) {
+        let record = StepRecord {
+            instruction_pos: self.instruction_pos,
+            head_position: self.tape.head_position,
+            writes,
+            read: None,
+            wrote_output: false,
+            executed: false,
+        };
+
+        if let Some(history) = &mut self.history {
+            history.push(record);
+        }
+    }
+
+    /// Number of steps to undo to get back to before the last remembered write of the cell,
+    /// None when no remembered step wrote it.
+    pub fn steps_since_write(&self, index: isize) -> Option<usize> {
+        let history = self.history.as_ref()?;
+
+        history.iter()
+            .rev()
+            .position(|record| record.writes.iter().any(|(written, _)| *written == index))
+            .map(|age| age + 1)
+    }
+
+    /// Executes only the next instruction, without checking the limits.
+    ///
+    /// Returns false when the program has already ended.
+    pub fn step(&mut self) -> Result<bool, RuntimeError> {
+        if self.is_finished() {
+            return Ok(false);
+        }
+
+        self.instruction_pos = self.execute_step()?;
+
+        Ok(true)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.instruction_pos >= self.instructions.len()
+    }
+
+    /// Runs the loaded program until it ends, a limit is reached or the tape reports an error.
+    ///
+    /// The limits apply to each call, so a run stopped by a limit can be continued
+    /// by calling `run` again.
+    pub fn run(&mut self) -> Result<RunStatus, RuntimeError> {
+        let started = Instant::now();
+        let first_step = self.statistics.steps;
+
         self.print_tape();
         self.print_command(false);
 
-        while self.command_pos < self.command.len() {
-            let ch = self.command[self.command_pos];
+        while self.instruction_pos < self.instructions.len() {
+            self.status = self.check_limits(started, first_step);
+            if self.status != RunStatus::Finished {
+                self.flush_output()?;
+                return Ok(self.status);
+            }
 
-            match ch {
-                '>' => self.tape.move_right(),
-                '<' => self.tape.move_left(),
-                '+' => self.tape.increment(),
-                '-' => self.tape.decrement(),
+            let is_input = self.instructions[self.instruction_pos] == Instruction::In;
+            let next_pos = self.execute_step()?;
 
-                '[' => self.act_on_lbracket(),
-                ']' => self.act_on_rbracket(),
+            if is_input {
+                self.clear();
+                self.print_tape();
+            } else {
+                self.wait();
+                self.clear();
+                self.print_tape();
+                self.print_command(true);
+            }
 
-                ',' => {
-                            self.get_input();
-                            self.clear();
-                            self.print_tape();
+            self.instruction_pos = next_pos;
+        }
 
-                            self.command_pos += 1;
-                            
-                            continue;
-                        },
+        self.status = RunStatus::Finished;
+        self.flush_output()?;
 
-                '.' =>  self.ouput.push(self.tape.get_current_value() as char),
+        Ok(self.status)
+    }
 
-                _   =>    {
-                            self.command_pos += 1;
-                            continue;
-                          },
-            };
+    /// Position of the next instruction in the command.
+    pub fn current_position(&self) -> usize {
+        match self.spans.get(self.instruction_pos) {
+            Some(span) => span.start,
+            None => self.command.len(),
+        }
+    }
 
-            self.wait();
-            self.clear();
-            self.print_tape();
-            self.print_command(true);
+    /// Line and column of the next instruction.
+    pub fn current_line_and_column(&self) -> (usize, usize) {
+        let position = self.current_position();
 
-            self.command_pos += 1;
+        Span { start: position, end: position + 1 }.line_and_column(&self.command)
+    }
+
+    /// Position where the instruction covering the given position starts,
+    /// or the next instruction when it is a comment.
+    pub fn instruction_start(&self, position: usize) -> Option<usize> {
+        self.spans.iter()
+            .find(|span| span.end > position)
+            .map(|span| span.start)
+    }
+
+    /// Positions of the `[` of every loop around the next instruction, outermost first.
+    pub fn loop_stack(&self) -> Vec<usize> {
+        let mut stack = Vec::new();
+
+        for (pos, instruction) in self.instructions.iter().enumerate() {
+            // the target is right after the matching ]
+            if let Instruction::JumpIfZero(target) = instruction {
+                if pos < self.instruction_pos && self.instruction_pos < *target {
+                    stack.push(self.spans[pos].start);
+                }
+            }
         }
 
+        stack
+    }
+
+    pub fn source(&self) -> &[char] {
+        &self.command
+    }
+
+    pub fn tape(&self) -> &MemoryTape {
+        &self.tape
+    }
+
+    /// Current state of the tape, the output and the statistics.
+    pub fn result(&self) -> RunResult {
+        RunResult {
+            output: self.output_bytes.clone(),
+            tape: self.tape.cells().to_vec(),
+            tape_start: self.tape.first_index(),
+            head_position: self.tape.head_position,
+            statistics: self.statistics.clone(),
+            status: self.status,
+            position: self.current_position(),
+        }
+    }
+
+    /// State of the run so far, to be saved and continued later.
+    ///
+    /// The rest of an input file is read into memory to be part of the snapshot.
+    pub fn snapshot(&mut self) -> Snapshot {
+        // replayed bytes are read first, from the end
+        let mut pending_input: Vec<u8> = self.replayed_input.iter().rev().copied().collect();
+        let remaining_input = self.input.buffer_remaining();
+        let input_ended = remaining_input.is_some();
+        pending_input.extend(remaining_input.unwrap_or_default());
+
+        Snapshot {
+            config: Config { step_limit: None, time_limit: None, ..self.config.clone() },
+            source: String::from_iter(&self.command),
+            position: self.current_position(),
+            loop_stack: self.loop_stack(),
+            tape: self.tape.cells().to_vec(),
+            tape_start: self.tape.first_index(),
+            head_position: self.tape.head_position,
+            pending_input,
+            input_ended,
+            output: self.output_bytes.clone(),
+            statistics: self.statistics.clone(),
+        }
+    }
+
+    /// Continues the run saved in the snapshot, with its config.
+    ///
+    /// The pending input of the snapshot is read before `input`.
+    /// Output written before the snapshot is not written to `output` again.
+    pub fn from_snapshot<'a>(should_print_tape: bool,
+                             snapshot: &Snapshot,
+                             input: InputSource,
+                             output: Box<dyn Write>) -> Result<Interpreter, &'a str> {
+        const MISMATCH: &str = "Snapshot doesn't match its program.";
+
+        let program = Program::parse(&snapshot.source).map_err(|_| MISMATCH)?;
+
+        let mut interpreter = Interpreter::new(should_print_tape, &snapshot.config, input, output);
+        interpreter.load(&program);
+
+        interpreter.instruction_pos = if snapshot.position == interpreter.command.len() {
+            interpreter.instructions.len()
+        } else {
+            interpreter.spans.iter().position(|span| span.start == snapshot.position).ok_or(MISMATCH)?
+        };
+
+        if interpreter.loop_stack() != snapshot.loop_stack ||
+           !interpreter.tape.restore_cells(snapshot.tape_start, snapshot.tape.clone(), snapshot.head_position) {
+            return Err(MISMATCH);
+        }
+
+        interpreter.replayed_input = snapshot.pending_input.iter().rev().copied().collect();
+        interpreter.output_bytes = snapshot.output.clone();
+        interpreter.statistics = snapshot.statistics.clone();
+
+        Ok(interpreter)
+    }
+
+    // runs the command without showing the result screen
+    pub fn execute(&mut self, command: &str) -> Result<RunStatus, InterpretError> {
+        self.load(&Program::parse(command)?);
+
+        Ok(self.run()?)
+    }
+
+    pub fn interpret(&mut self, command: &str) -> Result<RunStatus, InterpretError> {
+        let status = self.execute(command)?;
+
         clear_terminal();
         println!("Result:\n");
         self.tape.print_tape_sniplet();
-        println!("Output: {}", self.ouput);
+        println!("Output: {}", String::from_utf8_lossy(&self.output_bytes));
+
+        if status != RunStatus::Finished {
+            let (line, column) = self.current_line_and_column();
+            println!("{} at line {}, column {}.", status, line, column);
+        }
+
+        Ok(status)
+    }
+}
+
+#[cfg(test)]
+mod nested_loop_tests {
+    use crate::interpreter::*;
+    use std::io;
+
+    fn run_program(command: &str) -> Interpreter {
+        let config = Config { optimization: OptimizationLevel::None, ..Config::default() };
+
+        let mut interpreter = Interpreter::new(false, &config, InputSource::from_bytes(b""), Box::new(io::sink()));
+        interpreter.execute(command).unwrap();
+
+        interpreter
+    }
+
+    #[test]
+    fn matching_brackets() {
+        let interpreter = run_program("+[[-]>[+]<]");
+
+        assert_eq!(interpreter.instructions[1], Instruction::JumpIfZero(11));
+        assert_eq!(interpreter.instructions[10], Instruction::JumpIfNotZero(2));
+        assert_eq!(interpreter.instructions[2], Instruction::JumpIfZero(5));
+        assert_eq!(interpreter.instructions[4], Instruction::JumpIfNotZero(3));
+    }
+
+    #[test]
+    fn skip_nested_loop() {
+        // the zero cell must skip the whole outer loop, not stop at the inner ]
+        let interpreter = run_program("[[-]>]+");
+
+        assert_eq!(interpreter.tape.head_position, 0);
+        assert_eq!(interpreter.tape.get_current_value(), 1);
+    }
+
+    #[test]
+    fn skip_deeply_nested_loop() {
+        let interpreter = run_program("[[[>+]<]>[+]]++");
+
+        assert_eq!(interpreter.tape.head_position, 0);
+        assert_eq!(interpreter.tape.get_current_value(), 2);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn skip_inner_loop_while_running_outer() {
+        // cell 1 stays zero, so the inner loop is skipped on every iteration
+        let mut interpreter = run_program("+++[>[-]<->>+<<]>>");
+
+        assert_eq!(interpreter.tape.get_current_value(), 3);
+        interpreter.tape.move_left().unwrap();
+        assert_eq!(interpreter.tape.get_current_value(), 0);
+    }
+
+    #[test]
+    fn reject_unbalanced_brackets() {
+        let mut interpreter = Interpreter::new(false, &Config::default(), InputSource::from_bytes(b""), Box::new(io::sink()));
+
+        assert!(interpreter.execute("+]").is_err());
+        assert!(interpreter.execute("[[-]").is_err());
+    }
+
+    #[test]
+    fn skip_loop_at_end_of_program() {
+        let interpreter = run_program("+>[[-]]");
+
+        assert_eq!(interpreter.tape.head_position, 1);
+        assert_eq!(interpreter.tape.get_current_value(), 0);
+    }
+
+    #[test]
+    fn nested_multiplication() {
+        // 2 * 3 * 4 computed with two nested loops
+        let interpreter = run_program("++[>+++[>++++<-]<-]>>.");
+
+        assert_eq!(interpreter.tape.get_current_value(), 24);
+        assert_eq!(interpreter.output_bytes, [24]);
+    }
+}
+
+#[cfg(test)]
+mod optimization_tests {
+    use crate::interpreter::*;
+    use std::io;
+
+    fn run_program(command: &str, optimization: OptimizationLevel) -> Interpreter {
+        let config = Config { optimization, ..Config::default() };
+
+        let mut interpreter = Interpreter::new(false, &config, InputSource::from_bytes(b""), Box::new(io::sink()));
+        interpreter.execute(command).unwrap();
+
+        interpreter
+    }
+
+    fn assert_same_result(command: &str) {
+        let naive = run_program(command, OptimizationLevel::None);
+        let optimized = run_program(command, OptimizationLevel::Idioms);
+
+        assert_eq!(naive.output_bytes, optimized.output_bytes);
+        assert_eq!(naive.tape.head_position, optimized.tape.head_position);
+        assert_eq!(naive.tape.cells(), optimized.tape.cells());
+    }
+
+    #[test]
+    fn clear_loop() {
+        assert_same_result("+++++[-]>++[+]");
+    }
+
+    #[test]
+    fn scan_loop() {
+        assert_same_result("+>+>+>>+<<<<[>]>>[<]");
+    }
+
+    #[test]
+    fn multiply_loop() {
+        assert_same_result("+++++[->+>+++<<]>>>++++[<<<-->>>-]");
+    }
+
+    #[test]
+    fn multiply_loop_at_clamped_edge() {
+        // the default config clamps the head, so the loop adds to cell 0 from cell 0
+        assert_same_result("+[-<+>]<.");
+        assert_eq!(run_program("+[-<+>]<.", OptimizationLevel::Idioms).output_bytes, [1]);
+    }
+
+    #[test]
+    fn multiply_loop_wraps() {
+        assert_same_result("++++++++++++++++[->++++++++++++++++++++<]>.");
+    }
+
+    #[test]
+    fn hello_world() {
+        let command = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+
+        assert_same_result(command);
+        assert_eq!(run_program(command, OptimizationLevel::Idioms).output_bytes, b"Hello World!\n");
+    }
+}
+
+#[cfg(test)]
+mod input_tests {
+    use crate::interpreter::*;
+    use std::io;
+
+    fn run_program(command: &str, input: &[u8]) -> Interpreter {
+        run_with_eof(command, input, EofBehavior::Unchanged)
+    }
+
+    fn run_with_eof(command: &str, input: &[u8], eof: EofBehavior) -> Interpreter {
+        let config = Config { eof, ..Config::default() };
+
+        let mut interpreter = Interpreter::new(false, &config, InputSource::from_bytes(input), Box::new(io::sink()));
+        interpreter.execute(command).unwrap();
+
+        interpreter
+    }
+
+    #[test]
+    fn cat() {
+        // the cell is cleared before reading, so it stays zero when the input ends
+        let interpreter = run_program(",[.[-],]", b"hello, world");
+        assert_eq!(interpreter.output_bytes, b"hello, world");
+    }
+
+    #[test]
+    fn shift_input() {
+        // add 13 to every character of the input
+        let interpreter = run_program(",[>+++++++++++++[<+>-]<.[-],]", b"NOP");
+
+        assert_eq!(interpreter.output_bytes, b"[\\]");
+    }
+
+    #[test]
+    fn binary_output() {
+        let interpreter = run_program(",.,.,.", b"\x80\xff\x00");
+
+        assert_eq!(interpreter.output_bytes, [0x80, 0xff, 0x00]);
+    }
+
+    #[test]
+    fn input_end_keeps_cell() {
+        let interpreter = run_program("+++,", b"");
+
+        assert_eq!(interpreter.tape.get_current_value(), 3);
+    }
+
+    #[test]
+    fn input_end_stores_zero() {
+        let interpreter = run_with_eof("+++,", b"", EofBehavior::Zero);
+        assert_eq!(interpreter.tape.get_current_value(), 0);
+
+        // cat relying on zero at the end of input
+        let interpreter = run_with_eof(",[.,]", b"abc", EofBehavior::Zero);
+        assert_eq!(interpreter.output_bytes, b"abc");
+    }
+
+    #[test]
+    fn input_end_stores_all_ones() {
+        let interpreter = run_with_eof("+++,", b"", EofBehavior::AllOnes);
+        assert_eq!(interpreter.tape.get_current_value(), 255);
+
+        // cat relying on -1 at the end of input
+        let interpreter = run_with_eof(",+[-.,+]", b"abc", EofBehavior::AllOnes);
+        assert_eq!(interpreter.output_bytes, b"abc");
+    }
+}
+
+#[cfg(test)]
+mod output_tests {
+    use crate::interpreter::*;
+
+    // accepts a few bytes, then fails like a pipe whose reader has gone
+    struct ClosedPipe {
+        space: usize,
+    }
+
+    impl Write for ClosedPipe {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.space == 0 {
+                return Err(io::Error::from(io::ErrorKind::BrokenPipe));
+            }
+
+            let written = buf.len().min(self.space);
+            self.space -= written;
+            Ok(written)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn closed_output_stops_run() {
+        let mut interpreter = Interpreter::new(false, &Config::default(), InputSource::from_bytes(b""), Box::new(ClosedPipe { space: 3 }));
+
+        let error = match interpreter.execute("+[.]") {
+            Err(InterpretError::Runtime(error)) => error,
+            _ => panic!("expected a runtime error"),
+        };
+
+        assert_eq!(error.error, RuntimeErrorKind::Output(io::ErrorKind::BrokenPipe));
+        assert_eq!(error.position, 2);
+        assert_eq!(interpreter.result().output, [1, 1, 1]);
+    }
+}
+
+#[cfg(test)]
+mod result_tests {
+    use crate::interpreter::*;
+    use std::io;
+
+    #[test]
+    fn run_result() {
+        let mut interpreter = Interpreter::new(false, &Config::default(), InputSource::from_bytes(b"ab"), Box::new(io::sink()));
+        interpreter.execute(",>,+.>[-]++").unwrap();
+
+        let result = interpreter.result();
+
+        assert_eq!(result.output, b"c");
+        assert_eq!(&result.tape[0..3], [97, 99, 2]);
+        assert_eq!(result.head_position, 2);
+        assert_eq!(result.statistics, Statistics { steps: 8, input_bytes: 2, output_bytes: 1 });
+    }
+
+    #[test]
+    fn walk_left_of_first_cell() {
+        let config = Config { tape_bounds: TapeBounds::bidirectional(100), ..Config::default() };
+
+        let mut interpreter = Interpreter::new(false, &config, InputSource::from_bytes(b""), Box::new(io::sink()));
+        interpreter.execute("+<<++[<+>-]<.").unwrap();
+
+        let result = interpreter.result();
+
+        assert_eq!(result.output, [2]);
+        assert_eq!(result.head_position, -3);
+        let cell = |index: isize| result.tape[(index - result.tape_start) as usize];
+        assert_eq!([cell(-3), cell(-2), cell(-1), cell(0)], [2, 0, 0, 1]);
+    }
+}
+
+#[cfg(test)]
+mod cell_width_tests {
+    use crate::interpreter::*;
+    use std::io;
+
+    fn run_with_cells(command: &str, cell_width: CellWidth, cell_overflow: CellOverflow) -> Result<Interpreter, InterpretError> {
+        let config = Config { cell_width, cell_overflow, eof: EofBehavior::AllOnes, ..Config::default() };
+
+        let mut interpreter = Interpreter::new(false, &config, InputSource::from_bytes(b""), Box::new(io::sink()));
+        interpreter.execute(command)?;
+
+        Ok(interpreter)
+    }
+
+    #[test]
+    fn detect_cell_size() {
+        // counts the increments until the cell wraps around to zero
+        let command = "+[>+<+]>";
+
+        let interpreter = run_with_cells(command, CellWidth::Bits8, CellOverflow::Wrap).unwrap();
+        assert_eq!(interpreter.tape.get_current_value(), 255);
+
+        let interpreter = run_with_cells(command, CellWidth::Bits16, CellOverflow::Wrap).unwrap();
+        assert_eq!(interpreter.tape.get_current_value(), 65_535);
+    }
+
+    #[test]
+    fn wide_multiply() {
+        let interpreter = run_with_cells("++++++++++++++++[->++++++++++++++++++++<]>", CellWidth::Bits16, CellOverflow::Wrap).unwrap();
+
+        assert_eq!(interpreter.tape.get_current_value(), 320);
+    }
+
+    #[test]
+    fn input_end_with_wide_cells() {
+        let interpreter = run_with_cells(",", CellWidth::Bits32, CellOverflow::Wrap).unwrap();
+        assert_eq!(interpreter.tape.get_current_value(), 4_294_967_295);
+
+        let interpreter = run_with_cells(",", CellWidth::Unbounded, CellOverflow::Wrap).unwrap();
+        assert_eq!(interpreter.tape.get_current_value(), -1);
+    }
+
+    #[test]
+    fn output_lowest_byte() {
+        let interpreter = run_with_cells("-.", CellWidth::Unbounded, CellOverflow::Wrap).unwrap();
+
+        assert_eq!(interpreter.output_bytes, [255]);
+    }
+
+    #[test]
+    fn overflow_stops_run() {
+        let error = match run_with_cells("+.\n>-", CellWidth::Bits8, CellOverflow::Error) {
+            Err(InterpretError::Runtime(error)) => error,
+            _ => panic!("expected a runtime error"),
+        };
+
+        assert_eq!(error.error, RuntimeErrorKind::Tape(TapeError::CellOverflow { index: 1 }));
+        assert_eq!((error.position, error.line, error.column), (4, 2, 2));
+    }
+
+    #[test]
+    fn optimized_loops_overflow() {
+        let error = match run_with_cells("+[+]+.", CellWidth::Bits8, CellOverflow::Error) {
+            Err(InterpretError::Runtime(error)) => error,
+            _ => panic!("expected a runtime error"),
+        };
+        assert_eq!(error.error, RuntimeErrorKind::Tape(TapeError::CellOverflow { index: 0 }));
+
+        // a negative unbounded cell is never counted down to zero
+        let config = Config { cell_width: CellWidth::Unbounded, step_limit: Some(1000), ..Config::default() };
+        let mut interpreter = Interpreter::new(false, &config, InputSource::from_bytes(b""), Box::new(io::sink()));
+        assert_eq!(interpreter.execute("-[-]+.").unwrap(), RunStatus::StepLimitExceeded);
+    }
+
+    #[test]
+    fn every_step_can_overflow() {
+        let error = match run_with_cells("-+", CellWidth::Bits8, CellOverflow::Error) {
+            Err(InterpretError::Runtime(error)) => error,
+            _ => panic!("expected a runtime error"),
+        };
+
+        assert_eq!(error.error, RuntimeErrorKind::Tape(TapeError::CellOverflow { index: 0 }));
+    }
+}
+
+#[cfg(test)]
+mod bounds_policy_tests {
+    use crate::interpreter::*;
+    use std::io;
+
+    fn run_with_policy(command: &str, bounds_policy: BoundsPolicy, optimization: OptimizationLevel) -> Result<Interpreter, InterpretError> {
+        let config = Config { bounds_policy, optimization, tape_bounds: TapeBounds { lowest: 0, highest: 9 }, ..Config::default() };
+
+        let mut interpreter = Interpreter::new(false, &config, InputSource::from_bytes(b""), Box::new(io::sink()));
+        interpreter.execute(command)?;
+
+        Ok(interpreter)
+    }
+
+    #[test]
+    fn wrap_to_last_cell() {
+        let interpreter = run_with_policy("+<+++", BoundsPolicy::Wrap, OptimizationLevel::Idioms).unwrap();
+
+        assert_eq!(interpreter.tape.head_position, 9);
+        assert_eq!(interpreter.tape.get_current_value(), 3);
+    }
+
+    #[test]
+    fn scan_wraps() {
+        // the scan starts at cell 5 and finds the zero cell 0 after wrapping
+        let command = ">+>+>+>+>+>+>+>+>+<<<<[>]";
+        for optimization in [OptimizationLevel::None, OptimizationLevel::Idioms] {
+            let interpreter = run_with_policy(command, BoundsPolicy::Wrap, optimization).unwrap();
+            assert_eq!(interpreter.tape.head_position, 0);
+        }
+    }
+
+    #[test]
+    fn abort_reports_position() {
+        let error = match run_with_policy("+\n>+<<", BoundsPolicy::Abort, OptimizationLevel::Idioms) {
+            Err(InterpretError::Runtime(error)) => error,
+            _ => panic!("expected a runtime error"),
+        };
+
+        assert_eq!(error.error, RuntimeErrorKind::Tape(TapeError::HeadOutOfBounds { index: -1 }));
+        assert_eq!((error.line, error.column), (2, 3));
+        assert_eq!(error.to_string(), "Head moved out of the tape to cell -1 at line 2, column 3.");
+    }
+
+    #[test]
+    fn skipped_multiply_loop() {
+        // the counter is zero, so the target left of the tape is never reached
+        for optimization in [OptimizationLevel::None, OptimizationLevel::Idioms] {
+            let interpreter = run_with_policy("[-<+>]+.", BoundsPolicy::Abort, optimization).unwrap();
+            assert_eq!(interpreter.output_bytes, [1]);
+        }
+    }
+
+    #[test]
+    fn every_step_reaches_the_edge() {
+        // the head is clamped at cell 0 before it moves back right
+        let interpreter = run_with_policy("<>+<.", BoundsPolicy::Clamp, OptimizationLevel::None).unwrap();
+        assert_eq!(interpreter.output_bytes, [0]);
+
+        let error = match run_with_policy("<>", BoundsPolicy::Abort, OptimizationLevel::None) {
+            Err(InterpretError::Runtime(error)) => error,
+            _ => panic!("expected a runtime error"),
+        };
+        assert_eq!(error.error, RuntimeErrorKind::Tape(TapeError::HeadOutOfBounds { index: -1 }));
+
+        // a run stops right past the edge, like single steps would
+        let error = match run_with_policy(">>>>>>>>>>>>", BoundsPolicy::Abort, OptimizationLevel::None) {
+            Err(InterpretError::Runtime(error)) => error,
+            _ => panic!("expected a runtime error"),
+        };
+        assert_eq!(error.error, RuntimeErrorKind::Tape(TapeError::HeadOutOfBounds { index: 10 }));
+    }
+}
+
+#[cfg(test)]
+mod limit_tests {
+    use crate::interpreter::*;
+    use std::io;
+
+    fn interpreter_with_limits(step_limit: Option<u64>, time_limit: Option<Duration>) -> Interpreter {
+        let config = Config { step_limit, time_limit, ..Config::default() };
+
+        Interpreter::new(false, &config, InputSource::from_bytes(b""), Box::new(io::sink()))
+    }
+
+    #[test]
+    fn step_limit() {
+        let mut interpreter = interpreter_with_limits(Some(100), None);
+
+        assert_eq!(interpreter.execute("+.\n+[]").unwrap(), RunStatus::StepLimitExceeded);
+
+        let result = interpreter.result();
+        assert_eq!(result.status, RunStatus::StepLimitExceeded);
+        assert_eq!(result.statistics.steps, 100);
+        assert_eq!(result.output, [1]);
+        assert_eq!(result.tape[0], 2);
+        assert_eq!(interpreter.current_line_and_column().0, 2);
+    }
+
+    #[test]
+    fn continue_after_step_limit() {
+        let mut interpreter = interpreter_with_limits(Some(6), None);
+        interpreter.load(&Program::parse("+>+>+>+>+>+.").unwrap());
+
+        assert_eq!(interpreter.run().unwrap(), RunStatus::StepLimitExceeded);
+        assert_eq!(interpreter.result().statistics.steps, 6);
+
+        // the second run gets 6 steps of its own
+        assert_eq!(interpreter.run().unwrap(), RunStatus::Finished);
+        assert_eq!(interpreter.result().statistics.steps, 12);
+        assert_eq!(interpreter.result().output, [1]);
+    }
+
+    #[test]
+    fn finish_within_limit() {
+        let mut interpreter = interpreter_with_limits(Some(3), None);
+
+        assert_eq!(interpreter.execute("+++").unwrap(), RunStatus::Finished);
+        assert_eq!(interpreter.result().position, 3);
+    }
+
+    #[test]
+    fn time_limit() {
+        let mut interpreter = interpreter_with_limits(None, Some(Duration::from_millis(50)));
+
+        assert_eq!(interpreter.execute("+[]").unwrap(), RunStatus::TimeLimitExceeded);
+        // stopped inside the endless loop
+        assert!([1, 2].contains(&interpreter.result().position));
+    }
+
+    #[test]
+    fn endless_scan() {
+        // the head stays on the first cell, which is never zero
+        let mut interpreter = interpreter_with_limits(Some(100), None);
+        assert_eq!(interpreter.execute("+[<]").unwrap(), RunStatus::StepLimitExceeded);
+        assert_eq!(interpreter.result().position, 1);
+
+        let mut interpreter = interpreter_with_limits(None, Some(Duration::from_millis(50)));
+        assert_eq!(interpreter.execute("+[<]").unwrap(), RunStatus::TimeLimitExceeded);
+    }
+
+    #[test]
+    fn scan_steps() {
+        let mut interpreter = interpreter_with_limits(None, None);
+        interpreter.execute("+>+>+<<[>]").unwrap();
+
+        // one step for every move of the scan
+        assert_eq!(interpreter.result().statistics.steps, 9);
+        assert_eq!(interpreter.result().head_position, 3);
+    }
+}
+
+#[cfg(test)]
+mod history_tests {
+    use crate::interpreter::*;
+    use std::io;
+
+    fn interpreter_with_history(command: &str, input: &[u8], max_entries: usize) -> Interpreter {
+        let config = Config { optimization: OptimizationLevel::None, ..Config::default() };
+
+        let mut interpreter = Interpreter::new(false, &config, InputSource::from_bytes(input), Box::new(io::sink()));
+        interpreter.load(&Program::parse(command).unwrap());
+        interpreter.enable_history(max_entries);
+
+        interpreter
+    }
+
+    #[test]
+    fn undo_to_start() {
+        let mut interpreter = interpreter_with_history("++[->+++<]>.", b"", 100);
+        interpreter.run().unwrap();
+
+        while interpreter.step_back() {}
+
+        let result = interpreter.result();
+        assert_eq!(result.position, 0);
+        assert_eq!(result.head_position, 0);
+        assert!(result.tape.iter().all(|cell| *cell == 0));
+        assert_eq!(result.statistics, Statistics::default());
+        assert!(result.output.is_empty());
+    }
+
+    #[test]
+    fn replay_input() {
+        let mut interpreter = interpreter_with_history(",.,.", b"ab", 100);
+
+        for _ in 0..3 {
+            interpreter.step().unwrap();
+        }
+        assert!(interpreter.step_back());
+        assert!(interpreter.step_back());
+
+        interpreter.run().unwrap();
+        assert_eq!(interpreter.result().output, b"ab");
+        assert_eq!(interpreter.result().statistics.input_bytes, 2);
+    }
+
+    #[test]
+    fn forget_old_steps() {
+        // the last + and > take three entries
+        let mut interpreter = interpreter_with_history("+>+>+>+", b"", 3);
+        interpreter.run().unwrap();
+
+        assert!(interpreter.step_back());
+        assert!(interpreter.step_back());
+        assert!(!interpreter.step_back());
+        assert_eq!(interpreter.result().position, 5);
+    }
+
+    #[test]
+    fn last_write() {
+        let mut interpreter = interpreter_with_history("+>+<", b"", 100);
+        interpreter.run().unwrap();
+
+        assert_eq!(interpreter.steps_since_write(0), Some(4));
+        assert_eq!(interpreter.steps_since_write(1), Some(2));
+        assert_eq!(interpreter.steps_since_write(2), None);
+    }
+}
+
+#[cfg(test)]
+mod profiling_tests {
+    use crate::interpreter::*;
+    use std::io;
+
+    fn profile(command: &str, optimization: OptimizationLevel) -> Profile {
+        let config = Config { optimization, ..Config::default() };
+
+        let mut interpreter = Interpreter::new(false, &config, InputSource::from_bytes(b""), Box::new(io::sink()));
+        interpreter.load(&Program::parse(command).unwrap());
+        interpreter.enable_profiling();
+        interpreter.run().unwrap();
+
+        interpreter.profile().unwrap()
+    }
+
+    #[test]
+    fn position_counts() {
+        let profile = profile("+++[>++<-] x", OptimizationLevel::None);
+
+        assert_eq!(profile.position_counts, [1, 1, 1, 1, 3, 3, 3, 3, 3, 3, 0, 0]);
+        assert_eq!(profile.total_steps, 17);
+    }
+
+    #[test]
+    fn loop_counts() {
+        let profile = profile("++[>+++[>+<-]<-]>>[-]", OptimizationLevel::None);
+
+        assert_eq!(profile.loops.len(), 3);
+
+        let outer = &profile.loops[0];
+        assert_eq!((outer.start, outer.end), (2, 16));
+        assert_eq!((outer.entries, outer.iterations), (1, 2));
+
+        let inner = &profile.loops[1];
+        assert_eq!((inner.entries, inner.iterations), (2, 6));
+        assert_eq!(inner.steps, 2 + 6 * 5);
+
+        let clear = &profile.loops[2];
+        assert_eq!((clear.entries, clear.iterations), (1, 6));
+
+        assert_eq!(profile.hottest_loops()[0].start, 2);
+    }
+
+    #[test]
+    fn optimized_loops() {
+        let profile = profile("++[-]", OptimizationLevel::Idioms);
+
+        // the clear loop is a single instruction
+        assert!(profile.loops.is_empty());
+        assert_eq!(profile.position_counts, [1, 1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn without_profiling() {
+        let mut interpreter = Interpreter::new(false, &Config::default(), InputSource::from_bytes(b""), Box::new(io::sink()));
+        interpreter.execute("+").unwrap();
+
+        assert!(interpreter.profile().is_none());
+    }
+}
+
+#[cfg(test)]
+mod json_trace_tests {
+    use crate::interpreter::*;
+    use std::{fs, io};
+
+    #[test]
+    fn trace_records() {
+        let file_name = std::env::temp_dir().join("bf_json_trace_test.jsonl");
+        let trace = fs::File::create(&file_name).unwrap();
+
+        let config = Config { optimization: OptimizationLevel::None, ..Config::default() };
+        let mut interpreter = Interpreter::new(false, &config, InputSource::from_bytes(b"a"), Box::new(io::sink()));
+        interpreter.load(&Program::parse(",+.>[-]").unwrap());
+        interpreter.enable_trace(Box::new(trace));
+        interpreter.run().unwrap();
+
+        let text = fs::read_to_string(&file_name).unwrap();
+        let records: Vec<&str> = text.lines().collect();
+
+        assert_eq!(records, [
+            "{\"step\":1,\"offset\":0,\"opcode\":\"in\",\"head\":0,\"before\":0,\"after\":97,\"read\":97,\"written\":null}",
+            "{\"step\":2,\"offset\":1,\"opcode\":\"add\",\"operand\":1,\"head\":0,\"before\":97,\"after\":98,\"read\":null,\"written\":null}",
+            "{\"step\":3,\"offset\":2,\"opcode\":\"out\",\"head\":0,\"before\":98,\"after\":98,\"read\":null,\"written\":98}",
+            "{\"step\":4,\"offset\":3,\"opcode\":\"move\",\"operand\":1,\"head\":0,\"before\":98,\"after\":98,\"read\":null,\"written\":null}",
+            "{\"step\":5,\"offset\":4,\"opcode\":\"jump_if_zero\",\"head\":1,\"before\":0,\"after\":0,\"read\":null,\"written\":null}",
+        ]);
+
+        fs::remove_file(file_name).unwrap();
+    }
+
+    #[test]
+    fn failed_step_record() {
+        let file_name = std::env::temp_dir().join("bf_json_trace_error_test.jsonl");
+        let trace = fs::File::create(&file_name).unwrap();
+
+        let config = Config { optimization: OptimizationLevel::None, cell_overflow: CellOverflow::Error, ..Config::default() };
+        let mut interpreter = Interpreter::new(false, &config, InputSource::from_bytes(b""), Box::new(io::sink()));
+        interpreter.load(&Program::parse(">-").unwrap());
+        interpreter.enable_trace(Box::new(trace));
+        assert!(interpreter.run().is_err());
+
+        let text = fs::read_to_string(&file_name).unwrap();
+        assert_eq!(text.lines().last(),
+                   Some("{\"step\":2,\"offset\":1,\"opcode\":\"add\",\"operand\":-1,\"head\":1,\"before\":0,\"after\":0,\"read\":null,\"written\":null,\"error\":\"Cell 1 overflowed\"}"));
+
+        fs::remove_file(file_name).unwrap();
+    }
+
+    #[test]
+    fn escaped_error() {
+        let message: String = "a \"quoted\" \\ path\n".chars().map(escape_json).collect();
+        assert_eq!(message, "a \\\"quoted\\\" \\\\ path\\u000a");
+    }
+
+    // fails every write, like a trace file on a full disk
+    struct FullDisk;
+
+    impl io::Write for FullDisk {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::from(io::ErrorKind::StorageFull))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn trace_write_error() {
+        let mut interpreter = Interpreter::new(false, &Config::default(), InputSource::from_bytes(b""), Box::new(io::sink()));
+        interpreter.load(&Program::parse("+>+").unwrap());
+        interpreter.enable_trace(Box::new(FullDisk));
+
+        let error = interpreter.run().err().unwrap();
+        assert_eq!(error.error, RuntimeErrorKind::Trace(io::ErrorKind::StorageFull));
+        assert_eq!(error.position, 0);
+    }
+}
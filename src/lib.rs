@@ -0,0 +1,98 @@
+//! Brainfuck interpreter with C and x86-64 assembly translators and an ELF executable writer.
+//!
+//! A program is parsed into a [`Program`], which can be run with [`run`]
+//! or step by step through an [`Interpreter`].
+//!
+//! ```
+//! use bf_interpreter::{parse, run, Config};
+//!
+//! let program = parse(",[.[-],]").unwrap();
+//! let result = run(&program, &Config::default(), &b"hi"[..], std::io::sink()).unwrap();
+//!
+//! assert_eq!(result.output, b"hi");
+//! assert_eq!(result.statistics.input_bytes, 2);
+//! ```
+
+pub mod asmtranslator;
+pub mod bracket_validator;
+mod c_code;
+pub mod config;
+pub mod coverage;
+pub mod ctranslator;
+pub mod debugger;
+pub mod elfwriter;
+mod history;
+pub mod input_source;
+pub mod interpreter;
+mod machine_code;
+pub mod memory_tape;
+pub mod native;
+pub mod optimizer;
+pub mod profiler;
+pub mod program;
+pub mod snapshot;
+
+use std::io::{Read, Write};
+
+pub use crate::bracket_validator::BracketError;
+pub use crate::config::Config;
+pub use crate::coverage::Coverage;
+pub use crate::ctranslator::CLayout;
+pub use crate::debugger::Debugger;
+pub use crate::input_source::{EofBehavior, InputSource};
+pub use crate::interpreter::{InterpretError, Interpreter, RunResult, RunStatus, RuntimeError, RuntimeErrorKind, Statistics};
+pub use crate::memory_tape::{BoundsPolicy, CellOverflow, CellWidth, TapeBounds};
+pub use crate::optimizer::OptimizationLevel;
+pub use crate::profiler::Profile;
+pub use crate::program::Program;
+pub use crate::snapshot::Snapshot;
+
+/// Parses and validates a Brainfuck program.
+///
+/// Fails with the line and column of the first unbalanced bracket.
+pub fn parse(source: &str) -> Result<Program, BracketError> {
+    Program::parse(source)
+}
+
+/// Runs a program to its end, reading `,` from `input` and writing `.` to `output`.
+///
+/// A run that reaches a step or time limit from `config` stops early,
+/// its result has a `status` telling which limit was exceeded.
+/// Fails when a cell overflows and `config` doesn't allow it to wrap,
+/// when the head leaves the tape under [`BoundsPolicy::Abort`],
+/// or when `output` can't be written, with [`RuntimeErrorKind::Output`].
+pub fn run(program: &Program,
+           config: &Config,
+           input: impl Read + 'static,
+           output: impl Write + 'static) -> Result<RunResult, RuntimeError> {
+    let mut interpreter = Interpreter::new(false, config, InputSource::from_reader(input), Box::new(output));
+
+    interpreter.load(program);
+    interpreter.run()?;
+
+    Ok(interpreter.result())
+}
+
+#[test]
+fn run_reports_write_errors() {
+    use std::io;
+
+    // a writer that fails like a closed pipe
+    struct ClosedPipe;
+
+    impl Write for ClosedPipe {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::from(io::ErrorKind::BrokenPipe))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let program = parse("+.").unwrap();
+    let error = run(&program, &Config::default(), io::empty(), ClosedPipe).unwrap_err();
+
+    assert_eq!(error.error, RuntimeErrorKind::Output(io::ErrorKind::BrokenPipe));
+    assert_eq!((error.line, error.column), (1, 2));
+}
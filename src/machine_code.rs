@@ -0,0 +1,93 @@
+use std::mem;
+
+// x86-64 machine code with jumps to labels, which are patched in once every label is placed
+#[derive(Default)]
+pub struct MachineCode {
+    pub bytes: Vec<u8>,
+    // position of every label in the code, None until it is placed
+    labels: Vec<Option<usize>>,
+    // positions of rel32 fields and the labels they jump to
+    jumps: Vec<(usize, usize)>,
+    // positions of imm32 fields holding the address of a label
+    addresses: Vec<(usize, usize)>,
+}
+
+impl MachineCode {
+    pub fn new_label(&mut self) -> usize {
+        self.labels.push(None);
+        self.labels.len() - 1
+    }
+
+    // places the label at the end of the code
+    pub fn bind(&mut self, label: usize) {
+        self.labels[label] = Some(self.bytes.len());
+    }
+
+    pub fn emit(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    pub fn emit_i32(&mut self, value: i32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn emit_u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    // a jump, call or conditional jump with a rel32 operand, opcode is everything before it
+    pub fn emit_jump(&mut self, opcode: &[u8], label: usize) {
+        self.emit(opcode);
+        self.jumps.push((self.bytes.len(), label));
+        self.emit_i32(0);
+    }
+
+    // the address of the label as an imm32 operand
+    pub fn emit_address(&mut self, label: usize) {
+        self.addresses.push((self.bytes.len(), label));
+        self.emit_u32(0);
+    }
+
+    fn label_position(&self, label: usize) -> usize {
+        self.labels[label].expect("every label is placed")
+    }
+
+    // the code loaded at the given address, with every jump and address filled in
+    pub fn finish(mut self, base_address: u32) -> Vec<u8> {
+        for (position, label) in mem::take(&mut self.jumps) {
+            // relative to the end of the rel32 field
+            let distance = self.label_position(label) as i64 - (position + 4) as i64;
+            self.bytes[position..position + 4].copy_from_slice(&(distance as i32).to_le_bytes());
+        }
+
+        for (position, label) in mem::take(&mut self.addresses) {
+            let address = base_address + self.label_position(label) as u32;
+            self.bytes[position..position + 4].copy_from_slice(&address.to_le_bytes());
+        }
+
+        self.bytes
+    }
+}
+
+#[test]
+fn patch_jumps_and_addresses() {
+    let mut code = MachineCode::default();
+    let start = code.new_label();
+    let end = code.new_label();
+
+    code.bind(start);
+    // jmp end
+    code.emit_jump(&[0xE9], end);
+    // mov esi, start
+    code.emit(&[0xBE]);
+    code.emit_address(start);
+    code.bind(end);
+    // jne start
+    code.emit_jump(&[0x0F, 0x85], start);
+
+    let bytes = code.finish(0x400000);
+
+    assert_eq!(bytes[..5], [0xE9, 5, 0, 0, 0]);
+    assert_eq!(bytes[5..10], [0xBE, 0x00, 0x00, 0x40, 0x00]);
+    assert_eq!(bytes[10..], [0x0F, 0x85, 0xF0, 0xFF, 0xFF, 0xFF]);
+}
@@ -1,11 +1,15 @@
-mod code_parser;
-mod memory_tape;
-mod interpreter;
-mod ctranslator;
+mod command_line;
 mod menu_controller;
 
+use crate::command_line::*;
 use crate::menu_controller::*;
 
 fn main() {
-    start_menu();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    if args.is_empty() {
+        start_menu();
+    } else {
+        std::process::exit(run_command_line(&args));
+    }
 }
@@ -1,148 +1,479 @@
-const INITIAL_POS: usize = 0;
-const FINAL_POS: usize = 30_000 - 1;
-const TAPE_SIZE: usize = 30_000 - 1;
+const CLASSIC_TAPE_SIZE: isize = 30_000;
 const CELLS_IN_SNIPLET: usize = 11;
 
+use std::fmt;
+use std::mem;
+
+/// Most cells a tape can have, as many as fit in memory when they are all allocated.
+pub const MAX_TAPE_CELLS: usize = isize::MAX as usize / mem::size_of::<i64>();
+
+/// Number of bits in a tape cell.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CellWidth {
+    Bits8,
+    Bits16,
+    Bits32,
+    /// signed 64-bit cells that never wrap
+    Unbounded,
+}
+
+/// What happens when a cell goes past its smallest or largest value.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CellOverflow {
+    Wrap,
+    Error,
+}
+
+/// Cell indices the head can reach, both ends included.
+///
+/// Cells are only allocated once the head gets to them, so wide bounds are cheap.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TapeBounds {
+    pub lowest: isize,
+    pub highest: isize,
+}
+
+/// What happens when the head moves past the lowest or highest cell.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BoundsPolicy {
+    /// the head stops at the edge
+    Clamp,
+    /// the tape is circular
+    Wrap,
+    /// the run stops with an error
+    Abort,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TapeError {
+    CellOverflow { index: isize },
+    /// index is the cell outside the tape the head moved to
+    HeadOutOfBounds { index: isize },
+}
+
 pub struct MemoryTape {
-    block: [u8; TAPE_SIZE],
-    pub head_position: usize,
+    // allocated cells, the first one has index first_index
+    block: Vec<i64>,
+    first_index: isize,
+    pub head_position: isize,
+    bounds: TapeBounds,
+    policy: BoundsPolicy,
+    width: CellWidth,
+    overflow: CellOverflow,
+    // old values of the cells written by the program, when recording
+    writes: Option<Vec<(isize, i64)>>,
+}
+
+impl TapeBounds {
+    /// 30 000 cells starting at index 0
+    pub fn classic() -> TapeBounds {
+        TapeBounds {
+            lowest: 0,
+            highest: CLASSIC_TAPE_SIZE - 1,
+        }
+    }
+
+    /// max_cells cells split evenly around index 0, at most MAX_TAPE_CELLS
+    pub fn bidirectional(max_cells: usize) -> TapeBounds {
+        let max_cells = max_cells.clamp(1, MAX_TAPE_CELLS) as isize;
+        let lowest = -(max_cells / 2);
+
+        TapeBounds {
+            lowest,
+            highest: lowest + max_cells - 1,
+        }
+    }
+
+    /// parses lowest:highest, like -1000:29999, of at most MAX_TAPE_CELLS cells
+    pub fn from_range(range: &str) -> Option<TapeBounds> {
+        let (lowest, highest) = range.split_once(':')?;
+        let bounds = TapeBounds {
+            lowest: lowest.trim().parse().ok()?,
+            highest: highest.trim().parse().ok()?,
+        };
+
+        let cells_count = bounds.highest.checked_sub(bounds.lowest)?.checked_add(1)?;
+        if bounds.lowest <= bounds.highest && cells_count as usize <= MAX_TAPE_CELLS {
+            Some(bounds)
+        } else {
+            None
+        }
+    }
+
+    pub fn cells_count(&self) -> usize {
+        (self.highest - self.lowest) as usize + 1
+    }
+
+    pub fn contains(&self, index: isize) -> bool {
+        (self.lowest..=self.highest).contains(&index)
+    }
+}
+
+impl Default for TapeBounds {
+    fn default() -> TapeBounds {
+        TapeBounds::classic()
+    }
+}
+
+impl BoundsPolicy {
+    pub fn from_name(name: &str) -> Option<BoundsPolicy> {
+        match name {
+            "clamp" => Some(BoundsPolicy::Clamp),
+            "wrap" => Some(BoundsPolicy::Wrap),
+            "abort" => Some(BoundsPolicy::Abort),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BoundsPolicy::Clamp => "clamp",
+            BoundsPolicy::Wrap => "wrap",
+            BoundsPolicy::Abort => "abort",
+        }
+    }
+}
+
+impl CellOverflow {
+    pub fn from_name(name: &str) -> Option<CellOverflow> {
+        match name {
+            "wrap" => Some(CellOverflow::Wrap),
+            "error" => Some(CellOverflow::Error),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CellOverflow::Wrap => "wrap",
+            CellOverflow::Error => "error",
+        }
+    }
+}
+
+impl CellWidth {
+    pub fn from_name(name: &str) -> Option<CellWidth> {
+        match name {
+            "8" => Some(CellWidth::Bits8),
+            "16" => Some(CellWidth::Bits16),
+            "32" => Some(CellWidth::Bits32),
+            "unbounded" => Some(CellWidth::Unbounded),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CellWidth::Bits8 => "8",
+            CellWidth::Bits16 => "16",
+            CellWidth::Bits32 => "32",
+            CellWidth::Unbounded => "unbounded",
+        }
+    }
+
+    pub fn max_value(self) -> i64 {
+        match self {
+            CellWidth::Bits8 => u8::MAX as i64,
+            CellWidth::Bits16 => u16::MAX as i64,
+            CellWidth::Bits32 => u32::MAX as i64,
+            CellWidth::Unbounded => i64::MAX,
+        }
+    }
+
+    /// value with every bit set, what -1 becomes in a cell
+    pub fn all_ones(self) -> i64 {
+        match self {
+            CellWidth::Unbounded => -1,
+            _ => self.max_value(),
+        }
+    }
+}
+
+impl fmt::Display for TapeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TapeError::CellOverflow { index } => write!(f, "Cell {} overflowed", index),
+            TapeError::HeadOutOfBounds { index } => write!(f, "Head moved out of the tape to cell {}", index),
+        }
+    }
+}
+
+impl Default for MemoryTape {
+    fn default() -> MemoryTape {
+        MemoryTape::new()
+    }
 }
 
 impl MemoryTape {
     pub fn new() -> MemoryTape {
+        MemoryTape::with_cells(CellWidth::Bits8, CellOverflow::Wrap)
+    }
+
+    pub fn with_cells(width: CellWidth, overflow: CellOverflow) -> MemoryTape {
+        MemoryTape::with_bounds(width, overflow, TapeBounds::classic(), BoundsPolicy::Clamp)
+    }
+
+    pub fn with_bounds(width: CellWidth, overflow: CellOverflow, bounds: TapeBounds, policy: BoundsPolicy) -> MemoryTape {
+        let head_position = 0.clamp(bounds.lowest, bounds.highest);
+
         MemoryTape {
-            block: [0; TAPE_SIZE],
-            head_position: 0,
+            block: vec![0],
+            first_index: head_position,
+            head_position,
+            bounds,
+            policy,
+            width,
+            overflow,
+            writes: None,
         }
     }
 
-    pub fn increment(&mut self) {
-        let current_value = self.block[self.head_position];
-        let should_overflow = current_value == 255;
+    pub fn bounds(&self) -> TapeBounds {
+        self.bounds
+    }
 
-        self.block[self.head_position] = if should_overflow {
-            0
-        }else{
-            current_value + 1
-        };
+    fn last_index(&self) -> isize {
+        self.first_index + self.block.len() as isize - 1
     }
 
-    pub fn decrement(&mut self) {
-        let current_value = self.block[self.head_position];
-        let should_overflow = current_value == 0;
+    fn block_pos(&self, index: isize) -> usize {
+        (index - self.first_index) as usize
+    }
 
-        self.block[self.head_position] = if should_overflow {
-            255
-        }else{
-            current_value - 1
-        };
+    // grows the allocated cells so they include index, which must be within bounds
+    fn allocate(&mut self, index: isize) {
+        let allocated = self.block.len() as isize;
+
+        if index < self.first_index {
+            // at least double the cells to keep growing cheap
+            let grow = (self.first_index - index)
+                .max(allocated)
+                .min(self.first_index - self.bounds.lowest);
+
+            let mut block = vec![0; grow as usize];
+            block.extend_from_slice(&self.block);
+
+            self.block = block;
+            self.first_index -= grow;
+        } else if index > self.last_index() {
+            let grow = (index - self.last_index())
+                .max(allocated)
+                .min(self.bounds.highest - self.last_index());
+
+            self.block.resize((allocated + grow) as usize, 0);
+        }
     }
 
-    pub fn move_left(&mut self) {
-        let current_position = self.head_position;
-        let can_move = current_position != INITIAL_POS;
+    pub fn width(&self) -> CellWidth {
+        self.width
+    }
 
-        self.head_position = if can_move {
-            current_position-1
-        } else {
-            current_position
-        };
+    fn fit_value(&self, value: i128, index: isize) -> Result<i64, TapeError> {
+        let max_value = self.width.max_value() as i128;
+
+        match (self.width, self.overflow) {
+            (CellWidth::Unbounded, _) => i64::try_from(value).map_err(|_| TapeError::CellOverflow { index }),
+            (_, CellOverflow::Wrap) => Ok(value.rem_euclid(max_value + 1) as i64),
+            (_, CellOverflow::Error) if (0..=max_value).contains(&value) => Ok(value as i64),
+            (_, CellOverflow::Error) => Err(TapeError::CellOverflow { index }),
+        }
     }
 
-    pub fn move_right(&mut self) {
-        let current_position = self.head_position;
-        let can_move = current_position != FINAL_POS;
+    pub fn increment(&mut self) -> Result<(), TapeError> {
+        self.add(1)
+    }
 
-        self.head_position = if can_move {
-            current_position+1
-        } else {
-            current_position
-        };
+    pub fn decrement(&mut self) -> Result<(), TapeError> {
+        self.add(-1)
+    }
+
+    pub fn add(&mut self, amount: i64) -> Result<(), TapeError> {
+        self.add_to(self.head_position, amount)
     }
 
-    pub fn _move_to(&mut self, pos: usize) {
-        let is_valid_position = (pos <= FINAL_POS) && (pos >= INITIAL_POS);
+    fn add_to(&mut self, index: isize, amount: i64) -> Result<(), TapeError> {
+        let pos = self.block_pos(index);
+        let current_value = self.block[pos] as i128;
 
-        self.head_position = if is_valid_position {
-            pos
-        } else {
-            self.head_position
+        let value = self.fit_value(current_value + amount as i128, index)?;
+        self.record_write(index);
+        self.block[pos] = value;
+
+        Ok(())
+    }
+
+    /// starts keeping the old value of every cell changed by add or set_cell_value
+    pub fn record_writes(&mut self) {
+        self.writes = Some(Vec::new());
+    }
+
+    /// cells changed since the last call, with their old values
+    pub fn take_writes(&mut self) -> Vec<(isize, i64)> {
+        match &mut self.writes {
+            Some(writes) => std::mem::take(writes),
+            None => Vec::new(),
+        }
+    }
+
+    fn record_write(&mut self, index: isize) {
+        let old_value = self.get_value_at(index);
+
+        if let Some(writes) = &mut self.writes {
+            writes.push((index, old_value));
+        }
+    }
+
+    /// adds to the cell offset cells away from the head without moving it
+    pub fn add_at(&mut self, offset: isize, amount: i64) -> Result<(), TapeError> {
+        let index = self.get_target_index(offset)?;
+        self.allocate(index);
+
+        self.add_to(index, amount)
+    }
+
+    // cell the head reaches after moving distance cells, following the bounds policy
+    fn get_target_index(&self, distance: isize) -> Result<isize, TapeError> {
+        let index = self.head_position.saturating_add(distance);
+        if self.bounds.contains(index) {
+            return Ok(index);
+        }
+
+        match self.policy {
+            BoundsPolicy::Clamp => Ok(index.clamp(self.bounds.lowest, self.bounds.highest)),
+            BoundsPolicy::Wrap => {
+                let cells_count = self.bounds.cells_count() as i128;
+                let wrapped = (index as i128 - self.bounds.lowest as i128).rem_euclid(cells_count);
+
+                Ok(self.bounds.lowest + wrapped as isize)
+            },
+            // a run of moves goes one way, so the head leaves the tape right past its edge
+            BoundsPolicy::Abort => Err(TapeError::HeadOutOfBounds {
+                index: index.clamp(self.bounds.lowest.saturating_sub(1), self.bounds.highest.saturating_add(1)),
+            }),
         }
     }
 
-    pub fn get_current_value(&self) -> u8 {
-        self.block[self.head_position]
+    pub fn move_by(&mut self, distance: isize) -> Result<(), TapeError> {
+        let position = self.get_target_index(distance)?;
+
+        self.allocate(position);
+        self.head_position = position;
+
+        Ok(())
     }
 
-    pub fn set_cell_value(&mut self, value: u8) {
-        self.block[self.head_position] = value;
+    pub fn move_left(&mut self) -> Result<(), TapeError> {
+        self.move_by(-1)
     }
 
-    fn get_cells_num_left(head_position: usize, cells_num: usize) -> usize {
-        if head_position < cells_num {
-            head_position
-        }else {
-            cells_num
+    pub fn move_right(&mut self) -> Result<(), TapeError> {
+        self.move_by(1)
+    }
+
+    /// moves the head to pos, positions outside the bounds are ignored
+    pub fn move_to(&mut self, pos: isize) {
+        if self.bounds.contains(pos) {
+            self.allocate(pos);
+            self.head_position = pos;
         }
     }
 
-    fn get_cells_num_right(head_position: usize, cells_num: usize) -> usize {
-        if head_position + cells_num > FINAL_POS {
-            FINAL_POS - head_position
-        }else {
-            cells_num
+    pub fn get_current_value(&self) -> i64 {
+        self.block[self.block_pos(self.head_position)]
+    }
+
+    // cells the head never reached are zero
+    pub fn get_value_at(&self, pos: isize) -> i64 {
+        if pos < self.first_index || pos > self.last_index() {
+            0
+        } else {
+            self.block[self.block_pos(pos)]
         }
     }
 
-    fn fill_segment(&self, segment: &mut [u8], mut start_pos: usize) {
-        let cells_in_segment = CELLS_IN_SNIPLET;
+    /// allocated cells, the first one has index first_index()
+    pub fn cells(&self) -> &[i64] {
+        &self.block
+    }
 
-        for i in 0..cells_in_segment {
-            segment[i] = self.block[start_pos];
-            start_pos += 1;
-        } 
+    pub fn first_index(&self) -> isize {
+        self.first_index
     }
 
-    fn count_digits(&self, num: usize) -> usize {
-        if num == 0 {
-            return 1;
+    /// positions outside the bounds are ignored, the write is never recorded
+    pub fn set_value_at(&mut self, pos: isize, value: i64) {
+        if self.bounds.contains(pos) {
+            self.allocate(pos);
+
+            let block_pos = self.block_pos(pos);
+            self.block[block_pos] = value;
         }
+    }
 
-        let mut num_mut = num;
-        let mut cnt = 0;
+    /// replaces the allocated cells, the first one gets index first_index,
+    /// returns false when the cells or the head don't fit the bounds or the cell width
+    pub fn restore_cells(&mut self, first_index: isize, cells: Vec<i64>, head_position: isize) -> bool {
+        if cells.is_empty() || !self.bounds.contains(first_index) {
+            return false;
+        }
 
-        while num_mut > 0 {
-            num_mut /= 10;
+        // cells running past the highest isize can't fit the bounds either
+        let last_index = match isize::try_from(cells.len() - 1).ok().and_then(|offset| first_index.checked_add(offset)) {
+            Some(last_index) => last_index,
+            None => return false,
+        };
+        let fits_width = cells.iter()
+            .all(|value| self.width == CellWidth::Unbounded || (0..=self.width.max_value()).contains(value));
 
-            cnt += 1;
+        if !self.bounds.contains(last_index) || !(first_index..=last_index).contains(&head_position) || !fits_width {
+            return false;
         }
 
-        return cnt;
+        self.block = cells;
+        self.first_index = first_index;
+        self.head_position = head_position;
+
+        true
     }
 
-    pub fn print_tape_sniplet(&self){
-        let mut segment = [0; CELLS_IN_SNIPLET];
+    pub fn set_cell_value(&mut self, value: i64) {
+        self.record_write(self.head_position);
 
-        let right_cells = MemoryTape::get_cells_num_right(self.head_position, CELLS_IN_SNIPLET/2);
-        let mut left_cells = MemoryTape::get_cells_num_left(self.head_position, CELLS_IN_SNIPLET/2);
+        let pos = self.block_pos(self.head_position);
+        self.block[pos] = value;
+    }
 
-        // when there are less cells on the right or left
-        if right_cells < CELLS_IN_SNIPLET/2 {
-            left_cells = CELLS_IN_SNIPLET - 1 - right_cells;
-        }
+    fn count_digits(&self, num: i64) -> usize {
+        // includes the sign of negative values
+        num.to_string().len()
+    }
+
+    // index of the first cell shown, keeping the head in the middle while possible
+    fn get_sniplet_start(&self) -> isize {
+        let cells_num = CELLS_IN_SNIPLET.min(self.bounds.cells_count()) as isize;
+
+        (self.head_position - cells_num / 2)
+            .min(self.bounds.highest - cells_num + 1)
+            .max(self.bounds.lowest)
+    }
 
-        let mut current_index = self.head_position - left_cells;
-        self.fill_segment(&mut segment, current_index);
+    pub fn print_tape_sniplet(&self){
+        let first_index = self.get_sniplet_start();
+        let segment: Vec<i64> = (0..CELLS_IN_SNIPLET.min(self.bounds.cells_count()) as isize)
+            .map(|offset| self.get_value_at(first_index + offset))
+            .collect();
         
         // build strings
         let mut cells_str = String::new();
         let mut indexes_str = String::new();
         let mut head_str = String::new();
 
-        for cell in segment {
+        for (offset, cell) in segment.into_iter().enumerate() {
+            let current_index = first_index + offset as isize;
+
             // calculate spaces
-            let cell_digits = self.count_digits(cell as usize);
-            let index_digits = self.count_digits(current_index as usize);
+            let cell_digits = self.count_digits(cell);
+            let index_digits = self.count_digits(current_index as i64);
 
             let cell_len = if cell_digits > index_digits {
                 cell_digits
@@ -201,8 +532,6 @@ impl MemoryTape {
                     }
                 }
             }
-
-            current_index += 1;
         }
 
         println!("{}", cells_str);
@@ -219,7 +548,7 @@ mod basic_operations_tests {
     fn basic_increment() {
         let mut tape = MemoryTape::new();
         tape.set_cell_value(0);
-        tape.increment();
+        tape.increment().unwrap();
 
         assert_eq!(tape.get_current_value(), 1);
     }
@@ -228,7 +557,7 @@ mod basic_operations_tests {
     fn overflow_increment() {
         let mut tape = MemoryTape::new();
         tape.set_cell_value(255);
-        tape.increment();
+        tape.increment().unwrap();
 
         assert_eq!(tape.get_current_value(), 0);
     }
@@ -237,7 +566,7 @@ mod basic_operations_tests {
     fn basic_decrement() {
         let mut tape = MemoryTape::new();
         tape.set_cell_value(5);
-        tape.decrement();
+        tape.decrement().unwrap();
 
         assert_eq!(tape.get_current_value(), 4);
     }
@@ -246,7 +575,7 @@ mod basic_operations_tests {
     fn overflow_decrement() {
         let mut tape = MemoryTape::new();
         tape.set_cell_value(0);
-        tape.decrement();
+        tape.decrement().unwrap();
 
         assert_eq!(tape.get_current_value(), 255);
     }
@@ -255,10 +584,10 @@ mod basic_operations_tests {
     fn basic_move_left_and_right() {
         let mut tape = MemoryTape::new();
 
-        tape.move_right();
+        tape.move_right().unwrap();
         assert_eq!(tape.head_position, 1);
 
-        tape.move_left();
+        tape.move_left().unwrap();
         assert_eq!(tape.head_position, 0);
     }
 
@@ -266,11 +595,216 @@ mod basic_operations_tests {
     fn out_of_range_move_left_and_right() {
         let mut tape = MemoryTape::new();
 
-        tape.move_left();
+        tape.move_left().unwrap();
         assert_eq!(tape.head_position, 0);
 
-        tape._move_to(29_999);
-        tape.move_right();
+        tape.move_to(29_999);
+        tape.move_right().unwrap();
         assert_eq!(tape.head_position, 29_999);
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod cell_width_tests {
+    use crate::memory_tape::*;
+
+    #[test]
+    fn wide_cells_wrap() {
+        let mut tape = MemoryTape::with_cells(CellWidth::Bits16, CellOverflow::Wrap);
+        tape.add(300).unwrap();
+        assert_eq!(tape.get_current_value(), 300);
+
+        tape.set_cell_value(65_535);
+        tape.increment().unwrap();
+        assert_eq!(tape.get_current_value(), 0);
+
+        let mut tape = MemoryTape::with_cells(CellWidth::Bits32, CellOverflow::Wrap);
+        tape.decrement().unwrap();
+        assert_eq!(tape.get_current_value(), 4_294_967_295);
+    }
+
+    #[test]
+    fn overflow_error() {
+        let mut tape = MemoryTape::with_cells(CellWidth::Bits8, CellOverflow::Error);
+        tape.move_right().unwrap();
+
+        assert_eq!(tape.decrement(), Err(TapeError::CellOverflow { index: 1 }));
+        assert_eq!(tape.get_current_value(), 0);
+
+        tape.set_cell_value(250);
+        assert!(tape.add(5).is_ok());
+        assert!(tape.increment().is_err());
+    }
+
+    #[test]
+    fn unbounded_cells() {
+        let mut tape = MemoryTape::with_cells(CellWidth::Unbounded, CellOverflow::Wrap);
+        tape.decrement().unwrap();
+        assert_eq!(tape.get_current_value(), -1);
+
+        tape.add(1_000_000_000_000).unwrap();
+        assert_eq!(tape.get_current_value(), 999_999_999_999);
+
+        tape.set_cell_value(i64::MAX);
+        assert!(tape.increment().is_err());
+    }
+
+    #[test]
+    fn all_ones() {
+        assert_eq!(CellWidth::Bits8.all_ones(), 255);
+        assert_eq!(CellWidth::Bits16.all_ones(), 65_535);
+        assert_eq!(CellWidth::Unbounded.all_ones(), -1);
+    }
+}
+
+#[cfg(test)]
+mod growable_tape_tests {
+    use crate::memory_tape::*;
+
+    #[test]
+    fn restore_cells() {
+        let mut tape = MemoryTape::with_bounds(CellWidth::Bits8, CellOverflow::Wrap, TapeBounds::bidirectional(10), BoundsPolicy::Clamp);
+
+        assert!(tape.restore_cells(-2, vec![1, 2, 3], -1));
+        assert_eq!(tape.get_current_value(), 2);
+        assert_eq!(tape.first_index(), -2);
+
+        assert!(!tape.restore_cells(-2, vec![256], -2));
+        assert!(!tape.restore_cells(-6, vec![0], -6));
+        assert!(!tape.restore_cells(0, vec![0, 0], 2));
+
+        let bounds = TapeBounds { lowest: isize::MAX - 1, highest: isize::MAX };
+        let mut tape = MemoryTape::with_bounds(CellWidth::Bits8, CellOverflow::Wrap, bounds, BoundsPolicy::Clamp);
+        assert!(!tape.restore_cells(isize::MAX - 1, vec![0, 0, 0], isize::MAX));
+    }
+
+    #[test]
+    fn negative_indices() {
+        let mut tape = MemoryTape::with_bounds(CellWidth::Bits8, CellOverflow::Wrap, TapeBounds::bidirectional(1000), BoundsPolicy::Clamp);
+
+        tape.move_by(-3).unwrap();
+        tape.add(7).unwrap();
+        tape.move_by(5).unwrap();
+        tape.increment().unwrap();
+
+        assert_eq!(tape.head_position, 2);
+        assert_eq!(tape.get_value_at(-3), 7);
+        assert_eq!(tape.get_value_at(2), 1);
+        assert_eq!(tape.get_value_at(-100), 0);
+        assert_eq!(tape.first_index(), -3);
+    }
+
+    #[test]
+    fn grows_on_demand() {
+        let mut tape = MemoryTape::with_bounds(CellWidth::Bits8, CellOverflow::Wrap, TapeBounds::bidirectional(2_000_000), BoundsPolicy::Clamp);
+
+        tape.move_by(10).unwrap();
+        assert!(tape.cells().len() < 100);
+
+        tape.move_by(-600_000).unwrap();
+        assert_eq!(tape.head_position, -599_990);
+        assert_eq!(tape.first_index(), -599_990);
+    }
+
+    #[test]
+    fn stops_at_maximum() {
+        let mut tape = MemoryTape::with_bounds(CellWidth::Bits8, CellOverflow::Wrap, TapeBounds::bidirectional(10), BoundsPolicy::Clamp);
+
+        tape.move_by(-100).unwrap();
+        assert_eq!(tape.head_position, -5);
+
+        tape.move_by(100).unwrap();
+        assert_eq!(tape.head_position, 4);
+        assert_eq!(tape.cells().len(), 10);
+    }
+
+    #[test]
+    fn bounds_from_range() {
+        assert_eq!(TapeBounds::from_range("-10:20"), Some(TapeBounds { lowest: -10, highest: 20 }));
+        assert_eq!(TapeBounds::from_range("5:1"), None);
+        assert_eq!(TapeBounds::from_range("5"), None);
+
+        // ranges too wide to count their cells
+        assert_eq!(TapeBounds::from_range("-9223372036854775808:9223372036854775807"), None);
+        assert_eq!(TapeBounds::from_range("-4611686018427387904:4611686018427387904"), None);
+        assert_eq!(TapeBounds::bidirectional(usize::MAX).cells_count(), MAX_TAPE_CELLS);
+    }
+
+    #[test]
+    fn sniplet_start() {
+        let mut tape = MemoryTape::with_bounds(CellWidth::Bits8, CellOverflow::Wrap, TapeBounds::bidirectional(100), BoundsPolicy::Clamp);
+        assert_eq!(tape.get_sniplet_start(), -5);
+
+        tape.move_by(-100).unwrap();
+        assert_eq!(tape.get_sniplet_start(), -50);
+
+        let tape = MemoryTape::new();
+        assert_eq!(tape.get_sniplet_start(), 0);
+    }
+}
+
+#[cfg(test)]
+mod bounds_policy_tests {
+    use crate::memory_tape::*;
+
+    fn tape_with_policy(policy: BoundsPolicy) -> MemoryTape {
+        MemoryTape::with_bounds(CellWidth::Bits8, CellOverflow::Wrap, TapeBounds { lowest: -2, highest: 7 }, policy)
+    }
+
+    #[test]
+    fn wrap_around() {
+        let mut tape = tape_with_policy(BoundsPolicy::Wrap);
+
+        tape.move_by(-3).unwrap();
+        assert_eq!(tape.head_position, 7);
+
+        tape.move_right().unwrap();
+        assert_eq!(tape.head_position, -2);
+
+        tape.move_by(25).unwrap();
+        assert_eq!(tape.head_position, 3);
+    }
+
+    #[test]
+    fn abort() {
+        let mut tape = tape_with_policy(BoundsPolicy::Abort);
+
+        tape.move_by(-2).unwrap();
+        assert_eq!(tape.move_left(), Err(TapeError::HeadOutOfBounds { index: -3 }));
+        assert_eq!(tape.head_position, -2);
+
+        assert_eq!(tape.add_at(10, 1), Err(TapeError::HeadOutOfBounds { index: 8 }));
+    }
+
+    #[test]
+    fn add_at_follows_policy() {
+        let mut tape = tape_with_policy(BoundsPolicy::Wrap);
+        tape.add_at(-3, 4).unwrap();
+        assert_eq!(tape.head_position, 0);
+        assert_eq!(tape.get_value_at(7), 4);
+
+        let mut tape = tape_with_policy(BoundsPolicy::Clamp);
+        tape.add_at(100, 4).unwrap();
+        assert_eq!(tape.get_value_at(7), 4);
+    }
+}
+
+#[cfg(test)]
+mod recording_tests {
+    use crate::memory_tape::*;
+
+    #[test]
+    fn record_old_values() {
+        let mut tape = MemoryTape::new();
+        tape.add(3).unwrap();
+
+        tape.record_writes();
+        tape.increment().unwrap();
+        tape.move_right().unwrap();
+        tape.set_cell_value(9);
+        tape.set_value_at(5, 1);
+
+        assert_eq!(tape.take_writes(), [(0, 3), (1, 0)]);
+        assert!(tape.take_writes().is_empty());
+    }
+}
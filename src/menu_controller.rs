@@ -1,8 +1,14 @@
 use std::io::{self, Write};
 use std::fs;
 
-use crate::ctranslator::*;
-use crate::interpreter::*;
+use bf_interpreter::config::*;
+use bf_interpreter::ctranslator::*;
+use bf_interpreter::debugger::*;
+use bf_interpreter::elfwriter::*;
+use bf_interpreter::input_source::*;
+use bf_interpreter::interpreter::*;
+use bf_interpreter::optimizer::*;
+use bf_interpreter::program::*;
 
 enum InputType {
     File,
@@ -51,10 +57,7 @@ fn get_input_from_file() -> String {
         .read_line(&mut file_name)
         .expect("Failed to get input!");
 
-    let  input = match fs::read_to_string(file_name.trim()) {
-        Ok(text) => text,
-        Err(_) => String::new(),
-    };
+    let  input = fs::read_to_string(file_name.trim()).unwrap_or_default();
 
     clear_terminal();
 
@@ -79,16 +82,47 @@ fn prepare_for_input_file() {
     clear_terminal();
 }
 
-fn interpret(print_tape: bool, prepare_fun: &dyn Fn()->(), input_fun: &dyn Fn()->String) {
+fn interpret(print_tape: bool, optimization: OptimizationLevel, prepare_fun: &dyn Fn(), input_fun: &dyn Fn()->String) {
     prepare_fun();
 
-    let mut interpreter = Interpreter::new(print_tape);
-    interpreter.interpret(&input_fun());
+    // the output is shown together with the tape when it is printed
+    let output: Box<dyn Write> = if print_tape { Box::new(io::sink()) } else { Box::new(io::stdout()) };
+
+    let config = Config { optimization, ..Config::default() };
+    let mut interpreter = Interpreter::new(print_tape, &config, InputSource::InteractiveDecimal, output);
+    if let Err(error) = interpreter.interpret(&input_fun()) {
+        println!("{}", error);
+    }
 
     wait_for_key();
 }
 
-fn create_translator() -> CTranslator {
+fn debug(prepare_fun: &dyn Fn(), input_fun: &dyn Fn()->String) {
+    prepare_fun();
+
+    let program = match Program::parse(&input_fun()) {
+        Ok(program) => program,
+        Err(error) => {
+            println!("{}", error);
+            wait_for_key();
+            return;
+        },
+    };
+
+    // every step can be stopped on, so loops are not replaced
+    let config = Config { optimization: OptimizationLevel::None, ..Config::default() };
+    let mut interpreter = Interpreter::new(false, &config, InputSource::InteractiveDecimal, Box::new(io::stdout()));
+    interpreter.load(&program);
+
+    println!("Type help for the list of commands.");
+    let mut debugger = Debugger::new(interpreter);
+    if debugger.run_session(&mut io::stdin().lock(), &mut io::stdout()).is_err() {
+        println!("Failed to get input!");
+    }
+}
+
+// asks for the output file until the translator can create it
+fn create_translator<T>(create: fn(&str, &Config) -> Result<T, &'static str>) -> T {
     loop {
         println!();
         print!("Enter output file path: ");
@@ -103,20 +137,33 @@ fn create_translator() -> CTranslator {
 
         prepare_for_code_input();
         
-        match CTranslator::new(&file_name.trim()) {
+        match create(file_name.trim(), &Config::default()) {
             Ok(translator) => return translator,
             Err(msg) => println!("{}",msg),
         };
     }
 }
 
-fn translate(prepare_fun: &dyn Fn()->(), input_fun: &dyn Fn()->String) {
-    let mut translator = create_translator();
+fn translate(prepare_fun: &dyn Fn(), input_fun: &dyn Fn()->String) {
+    let mut translator = create_translator(CTranslator::new);
 
     prepare_fun();
     
     match translator.translate(&input_fun()) {
-        Ok(_) => println!("C code successfully saved to {}", "file"),
+        Ok(_) => println!("C code successfully saved to file"),
+        Err(msg) => println!("{}", msg),
+    };
+
+    wait_for_key();
+}
+
+fn write_executable(prepare_fun: &dyn Fn(), input_fun: &dyn Fn()->String) {
+    let mut writer = create_translator(ElfWriter::new);
+
+    prepare_fun();
+
+    match writer.translate(&input_fun()) {
+        Ok(_) => println!("Executable successfully saved to file"),
         Err(msg) => println!("{}", msg),
     };
 
@@ -162,7 +209,7 @@ fn interpret_menu(input_type: InputType) {
     loop { 
         clear_terminal();
 
-        println!("Choose an option:\n 1. Back\n 2. Interpret with tape\n 3. Interpret without tape\n");
+        println!("Choose an option:\n 1. Back\n 2. Interpret with tape\n 3. Interpret without tape\n 4. Interpret without tape and optimizations\n 5. Debug\n");
         print!("Input: ");
         // flush to avoid delay in print
         io::stdout().flush().unwrap();
@@ -176,8 +223,11 @@ fn interpret_menu(input_type: InputType) {
         // exclude enter
         match &input[0..input.len()-2] {
             "1" => return,
-            "2" => interpret(true, &prepare_fun, &input_fun),
-            "3" => interpret(false, &prepare_fun, &input_fun),
+            // every step is shown when printing the tape, so loops are not replaced
+            "2" => interpret(true, OptimizationLevel::None, &prepare_fun, &input_fun),
+            "3" => interpret(false, OptimizationLevel::Idioms, &prepare_fun, &input_fun),
+            "4" => interpret(false, OptimizationLevel::None, &prepare_fun, &input_fun),
+            "5" => debug(&prepare_fun, &input_fun),
             _ => continue,
         };
     }
@@ -187,7 +237,7 @@ pub fn start_menu() {
     loop {
         clear_terminal();
         
-        println!("Choose an option:\n 1. Exit\n 2. Interpret\n 3. Translate to C\n");
+        println!("Choose an option:\n 1. Exit\n 2. Interpret\n 3. Translate to C\n 4. Translate to a Linux x86-64 executable\n");
         print!("Input: ");
         // flush to avoid delay in print
         io::stdout().flush().unwrap();
@@ -208,7 +258,7 @@ pub fn start_menu() {
                         };
                    }
                 
-            "3" => {
+            choice @ ("3" | "4") => {
                         let input_type = match file_or_input_menu() {
                                                                         Some(input_type) => input_type,
                                                                         None => continue,
@@ -223,7 +273,11 @@ pub fn start_menu() {
                                                             InputType::File => prepare_for_input_file,
                                                            };
 
-                        translate(&prepare_fun, &input_fun);
+                        if choice == "3" {
+                            translate(&prepare_fun, &input_fun);
+                        } else {
+                            write_executable(&prepare_fun, &input_fun);
+                        }
                    }   
             _ => continue,
         };
@@ -0,0 +1,358 @@
+use crate::config::*;
+use crate::ctranslator::*;
+
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{self, Child, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread::{self, JoinHandle};
+
+// numbers the files of the programs built by this process
+static BUILD_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// C compiler used to build translated programs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CCompiler {
+    /// Program run to compile, found through PATH unless it is a path.
+    pub command: String,
+    pub flags: Vec<String>,
+}
+
+impl Default for CCompiler {
+    fn default() -> CCompiler {
+        CCompiler {
+            command: String::from("cc"),
+            flags: vec![String::from("-O2")],
+        }
+    }
+}
+
+/// Why a program couldn't be built or run.
+pub enum NativeError<'a> {
+    Translate(TranslateError<'a>),
+    /// What the compiler reported.
+    Compile(String),
+    Run(&'a str),
+}
+
+impl fmt::Display for NativeError<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NativeError::Translate(error) => write!(f, "{}", error),
+            NativeError::Compile(msg) => write!(f, "{}", msg),
+            NativeError::Run(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+/// Output of a run of a compiled program.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NativeRun {
+    pub output: Vec<u8>,
+    /// What the program wrote to stderr, like the message of a head leaving the tape.
+    pub errors: Vec<u8>,
+    /// None when the program was stopped by a signal.
+    pub exit_code: Option<i32>,
+}
+
+/// A program translated to C and compiled into the temporary directory,
+/// the executable is removed once this is dropped.
+pub struct NativeProgram {
+    binary: PathBuf,
+}
+
+impl CCompiler {
+    /// Compiles the C file into an executable.
+    pub fn compile<'a>(&self, c_file: &Path, binary: &Path) -> Result<(), NativeError<'a>> {
+        let output = Command::new(&self.command)
+            .args(&self.flags)
+            .arg("-o")
+            .arg(binary)
+            .arg(c_file)
+            .output()
+            .map_err(|_| NativeError::Compile(String::from("Couldn't start the C compiler ") + &self.command + "."))?;
+
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(NativeError::Compile(String::from("The C compiler failed:\n") + &String::from_utf8_lossy(&output.stderr)))
+        }
+    }
+}
+
+impl NativeProgram {
+    /// Translates the command with the settings in config and compiles it.
+    pub fn build<'a>(command: &str, config: &Config, compiler: &CCompiler) -> Result<NativeProgram, NativeError<'a>> {
+        let name = String::from("bf_native_") + &process::id().to_string() + "_" +
+                   &BUILD_COUNT.fetch_add(1, Ordering::Relaxed).to_string();
+        let c_file = std::env::temp_dir().join(name.clone() + ".c");
+        let binary = std::env::temp_dir().join(name);
+
+        let mut translator = CTranslator::new(c_file.to_str().unwrap_or_default(), config)
+            .map_err(|msg| NativeError::Translate(TranslateError::Write(msg)))?;
+        let translated = translator.translate(command).map_err(NativeError::Translate);
+
+        let result = translated.and_then(|_| compiler.compile(&c_file, &binary));
+        // the C file is only needed by the compiler
+        let _ = fs::remove_file(&c_file);
+        result?;
+
+        Ok(NativeProgram { binary })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.binary
+    }
+
+    // starts the program and writes the input to it from another thread when its stdin is piped,
+    // so a program that fills its output pipe before reading all of its input can't block the writer
+    fn start<'a>(&self, input: Option<&[u8]>, output: Stdio, errors: Stdio) -> Result<(Child, Option<JoinHandle<()>>), NativeError<'a>> {
+        let mut child = Command::new(&self.binary)
+            .stdin(if input.is_some() { Stdio::piped() } else { Stdio::inherit() })
+            .stdout(output)
+            .stderr(errors)
+            .spawn()
+            .map_err(|_| NativeError::Run("Couldn't start the compiled program."))?;
+
+        // a program that ends without reading all of its input closes the pipe early,
+        // and dropping stdin lets it see the end of the input
+        let writer = match (child.stdin.take(), input) {
+            (Some(mut stdin), Some(input)) => {
+                let input = input.to_vec();
+                Some(thread::spawn(move || {
+                    let _ = stdin.write_all(&input);
+                }))
+            },
+            _ => None,
+        };
+
+        Ok((child, writer))
+    }
+
+    /// Runs the program with the given input, collecting what it writes.
+    pub fn run<'a>(&self, input: &[u8]) -> Result<NativeRun, NativeError<'a>> {
+        let (child, writer) = self.start(Some(input), Stdio::piped(), Stdio::piped())?;
+        let output = child.wait_with_output().map_err(|_| NativeError::Run("Couldn't read the output of the compiled program."))?;
+        if let Some(writer) = writer {
+            let _ = writer.join();
+        }
+
+        Ok(NativeRun {
+            output: output.stdout,
+            errors: output.stderr,
+            exit_code: output.status.code(),
+        })
+    }
+
+    /// Runs the program writing to output and to stderr, it reads stdin when there is no input.
+    /// Returns the exit code, None when the program was stopped by a signal.
+    pub fn run_attached<'a>(&self, input: Option<&[u8]>, output: Stdio) -> Result<Option<i32>, NativeError<'a>> {
+        let (mut child, writer) = self.start(input, output, Stdio::inherit())?;
+        let status = child.wait().map_err(|_| NativeError::Run("Couldn't wait for the compiled program."))?;
+        if let Some(writer) = writer {
+            let _ = writer.join();
+        }
+
+        Ok(status.code())
+    }
+}
+
+impl Drop for NativeProgram {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.binary);
+    }
+}
+
+#[cfg(test)]
+mod native_tests {
+    use crate::ctranslator::skip_without_cc;
+    use crate::native::*;
+
+    #[test]
+    fn compiler_errors() {
+        let compiler = CCompiler { command: String::from("/nonexistent/cc"), flags: Vec::new() };
+        let error = NativeProgram::build("+", &Config::default(), &compiler).err().unwrap();
+        assert_eq!(error.to_string(), "Couldn't start the C compiler /nonexistent/cc.");
+
+        let error = NativeProgram::build("+]", &Config::default(), &CCompiler::default()).err().unwrap();
+        assert!(matches!(error, NativeError::Translate(TranslateError::Syntax(_))));
+    }
+
+    #[test]
+    fn build_and_run() {
+        if skip_without_cc("build_and_run") {
+            return;
+        }
+
+        let program = NativeProgram::build(",[.,]", &Config::default(), &CCompiler::default()).unwrap_or_else(|error| panic!("{}", error));
+
+        let output_file = std::env::temp_dir().join(String::from("bf_native_output_") + &process::id().to_string());
+        let exit_code = program.run_attached(Some(b"xyz\0"), Stdio::from(fs::File::create(&output_file).unwrap()));
+        assert_eq!(exit_code.unwrap_or_else(|error| panic!("{}", error)), Some(0));
+        assert_eq!(fs::read(&output_file).unwrap(), b"xyz");
+        fs::remove_file(&output_file).unwrap();
+
+        let path = program.path().to_path_buf();
+        assert_eq!(program.run(b"abc\0").unwrap_or_else(|error| panic!("{}", error)), NativeRun { output: b"abc".to_vec(), errors: Vec::new(), exit_code: Some(0) });
+
+        drop(program);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn run_with_large_input() {
+        if skip_without_cc("run_with_large_input") {
+            return;
+        }
+
+        let program = NativeProgram::build(",[.,]", &Config::default(), &CCompiler::default()).unwrap_or_else(|error| panic!("{}", error));
+
+        // far more than a pipe holds, the program writes its output while the input is still being written
+        let input: Vec<u8> = (0..1 << 20).map(|n| (n % 255 + 1) as u8).chain([0]).collect();
+        let run = program.run(&input).unwrap_or_else(|error| panic!("{}", error));
+        assert_eq!(run.output, input[..input.len() - 1]);
+        assert_eq!(run.exit_code, Some(0));
+    }
+}
+
+// the corpus and the settings are shared with the tests of the assembly and ELF backends
+#[cfg(test)]
+pub(crate) mod differential_tests {
+    use crate::ctranslator::skip_without_cc;
+    use crate::native::*;
+    use crate::input_source::*;
+    use crate::interpreter::*;
+    use crate::memory_tape::*;
+    use crate::optimizer::*;
+    use crate::program::*;
+    use std::io;
+
+    // programs with the input they are run on
+    pub(crate) type Corpus = &'static [(&'static str, &'static [u8])];
+
+    // the input ends in a zero byte when the program reads until it gets one,
+    // as the end of the input differs between settings
+    pub(crate) const CORPUS: Corpus = &[
+        // hello world
+        ("++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.", b""),
+        // cat
+        (",[.,]", b"differential\n\0"),
+        // reverse the input
+        (">,[>,]<[.<]", b"stressed\0"),
+        // print the input as decimal numbers
+        (",[>>++++++++++<<[->+>-[>+>>]>[+[-<+>]>+>>]<<<<<<]>>[-]>>>++++++++++<[->-[>+>>]>[+[-<+>]>+>>]<<<<<]>[-]>>[>++++++[-<++++++++>]<.<<+>+>[-]]<[<[->-<]++++++[->++++++++<]>.[-]]<<++++++[-<++++++++>]<.[-]<<[-<+>],]", b"\x07\x2a\xff\0"),
+        // nested multiply and clear loops
+        ("+++[>+++++[>+++++<-]<-]>>[->+>++<<]>>.<.<<[-]+.", b""),
+        // scans in both directions
+        (">+>+>+>+>>+>+<<<<<<[>]>[>]<[<]<[<]>.>>>.", b""),
+        // cells that wrap both ways, 256 is zero only in byte cells
+        ("-.+.[-]+++[->---<]>.>++++++++++++++++[<++++++++++++++++>-]<[>>+<<[-]]>>.", b""),
+        // the input ends before the program stops reading
+        (",.,.,.", b"a"),
+        // a move past the left end of the tape between changes of two cells
+        ("<+>+.<.", b""),
+        // a multiply loop that never runs, its target left of the tape is never reached
+        ("[-<+>]+.", b""),
+    ];
+
+    // programs leaving the tape, which only end when the head wraps around or the run is aborted
+    pub(crate) const EDGE_CORPUS: Corpus = &[
+        // scans off the left end and, on a tape of 16 cells, off the right end
+        ("+[<]+.", b""),
+        ("+>+>+>+>+>+>+>+[>]+.", b""),
+        // a multiply loop with its target left of the tape
+        ("+[-<+>]<.", b""),
+    ];
+
+    // programs stopped at the ends of the tape, which only end when the head is clamped
+    pub(crate) const CLAMPED_EDGE_CORPUS: Corpus = &[
+        // multiply loops with their targets left of the tape
+        ("+[-<+>]<.", b""),
+        ("+[-<<+>>]<<.>.", b""),
+        // a loop that adds one to the right end cell until it wraps to zero
+        ("+[>+]<.>.", b""),
+    ];
+
+    // what a translated program does: the same output, and the same message and exit code
+    // when the head leaves the tape
+    pub(crate) fn interpret(command: &str, config: &Config, input: &[u8]) -> NativeRun {
+        let mut interpreter = Interpreter::new(false, config, InputSource::from_bytes(input), Box::new(io::sink()));
+        interpreter.load(&Program::parse(command).unwrap());
+
+        let (errors, exit_code) = match interpreter.run() {
+            Ok(_) => (Vec::new(), 0),
+            Err(error) => ((error.to_string() + "\n").into_bytes(), 4),
+        };
+
+        NativeRun { output: interpreter.result().output, errors, exit_code: Some(exit_code) }
+    }
+
+    // runs every program of the corpus translated by a backend and as interpreted
+    pub(crate) fn compare(corpus: Corpus, config: &Config, run: &dyn Fn(&str, &Config, &[u8]) -> NativeRun) {
+        for (command, input) in corpus {
+            assert_eq!(run(command, config, input), interpret(command, config, input), "run of {} with {:?}", command, config);
+        }
+    }
+
+    // settings every backend is compared under, with the corpus run under each
+    pub(crate) fn settings() -> Vec<(Corpus, Config)> {
+        let mut settings = Vec::new();
+
+        for optimization in [OptimizationLevel::None, OptimizationLevel::Idioms] {
+            settings.push((CORPUS, Config { optimization, ..Config::default() }));
+        }
+        for cell_width in [CellWidth::Bits16, CellWidth::Bits32, CellWidth::Unbounded] {
+            settings.push((CORPUS, Config { cell_width, ..Config::default() }));
+        }
+        for eof in [EofBehavior::Zero, EofBehavior::AllOnes] {
+            settings.push((CORPUS, Config { eof, ..Config::default() }));
+        }
+
+        for bounds_policy in [BoundsPolicy::Wrap, BoundsPolicy::Abort] {
+            settings.push((CORPUS, Config { tape_bounds: TapeBounds::bidirectional(16), bounds_policy, ..Config::default() }));
+
+            for tape_bounds in [TapeBounds::classic(), TapeBounds::bidirectional(16)] {
+                for optimization in [OptimizationLevel::None, OptimizationLevel::Idioms] {
+                    settings.push((EDGE_CORPUS, Config { tape_bounds, bounds_policy, optimization, ..Config::default() }));
+                }
+            }
+        }
+        for tape_bounds in [TapeBounds::classic(), TapeBounds::bidirectional(16)] {
+            for optimization in [OptimizationLevel::None, OptimizationLevel::Idioms] {
+                settings.push((CLAMPED_EDGE_CORPUS, Config { tape_bounds, bounds_policy: BoundsPolicy::Clamp, optimization, ..Config::default() }));
+            }
+        }
+
+        settings
+    }
+
+    fn build_and_run(command: &str, config: &Config, input: &[u8]) -> NativeRun {
+        let program = NativeProgram::build(command, config, &CCompiler::default()).unwrap_or_else(|error| panic!("{}", error));
+
+        program.run(input).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    #[test]
+    fn nested_layout() {
+        if skip_without_cc("nested_layout") {
+            return;
+        }
+
+        for optimization in [OptimizationLevel::None, OptimizationLevel::Idioms] {
+            compare(CORPUS, &Config { c_layout: CLayout::Nested, optimization, ..Config::default() }, &build_and_run);
+        }
+    }
+
+    #[test]
+    fn shared_settings() {
+        if skip_without_cc("shared_settings") {
+            return;
+        }
+
+        for (corpus, config) in settings() {
+            compare(corpus, &config, &build_and_run);
+        }
+    }
+}
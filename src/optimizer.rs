@@ -0,0 +1,184 @@
+use crate::memory_tape::*;
+use crate::program::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OptimizationLevel {
+    /// run the program as parsed
+    None,
+    /// replace clear, scan and multiply loops with single operations
+    Idioms,
+}
+
+impl OptimizationLevel {
+    pub fn from_name(name: &str) -> Option<OptimizationLevel> {
+        match name {
+            "none" => Some(OptimizationLevel::None),
+            "idioms" => Some(OptimizationLevel::Idioms),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            OptimizationLevel::None => "none",
+            OptimizationLevel::Idioms => "idioms",
+        }
+    }
+}
+
+fn as_clear(body: &[Op]) -> Option<OpKind> {
+    match body {
+        [Op { kind: OpKind::Add(-1), .. }] | [Op { kind: OpKind::Add(1), .. }] => Some(OpKind::Clear),
+        _ => None,
+    }
+}
+
+fn as_scan(body: &[Op]) -> Option<OpKind> {
+    match body {
+        [Op { kind: OpKind::Move(distance), .. }] if *distance != 0 => Some(OpKind::Scan(*distance)),
+        _ => None,
+    }
+}
+
+fn as_mul_add(body: &[Op]) -> Option<OpKind> {
+    let mut offset: isize = 0;
+    // (offset, amount) added to each touched cell during one iteration
+    let mut changes: Vec<(isize, i32)> = Vec::new();
+
+    for op in body {
+        match op.kind {
+            OpKind::Move(distance) => offset += distance,
+            OpKind::Add(amount) => match changes.iter_mut().find(|change| change.0 == offset) {
+                Some(change) => change.1 += amount,
+                None => changes.push((offset, amount)),
+            },
+            _ => return None,
+        }
+    }
+
+    // the loop must return to its cell and count it down by one
+    let counter = changes.iter().position(|change| change.0 == 0)?;
+    if offset != 0 || changes[counter].1 != -1 {
+        return None;
+    }
+    changes.remove(counter);
+    changes.retain(|change| change.1 != 0);
+
+    Some(OpKind::MulAdd(changes))
+}
+
+// clear and multiply loops are only replaced when cells wrap around modulo a fixed width,
+// as otherwise they can overflow or never end. multiply loops also need a head that is never
+// clamped, since a clamped loop keeps adding to the edge cell from the cell next to it
+fn optimize_ops(ops: Vec<Op>, wrapping_cells: bool, clamped_head: bool) -> Vec<Op> {
+    ops.into_iter()
+        .map(|op| match op.kind {
+            OpKind::Loop(body) => {
+                let body = optimize_ops(body, wrapping_cells, clamped_head);
+
+                let arithmetic = if !wrapping_cells {
+                    None
+                } else if clamped_head {
+                    as_clear(&body)
+                } else {
+                    as_clear(&body).or_else(|| as_mul_add(&body))
+                };
+
+                let kind = arithmetic
+                    .or_else(|| as_scan(&body))
+                    .unwrap_or(OpKind::Loop(body));
+
+                Op { kind, span: op.span }
+            },
+            _ => op,
+        })
+        .collect()
+}
+
+pub fn optimize(program: Program, level: OptimizationLevel, cell_width: CellWidth, cell_overflow: CellOverflow,
+                bounds_policy: BoundsPolicy) -> Program {
+    let wrapping_cells = cell_width != CellWidth::Unbounded && cell_overflow == CellOverflow::Wrap;
+    let clamped_head = bounds_policy == BoundsPolicy::Clamp;
+
+    match level {
+        OptimizationLevel::None => program,
+        OptimizationLevel::Idioms => Program { ops: optimize_ops(program.ops, wrapping_cells, clamped_head), source: program.source },
+    }
+}
+
+#[cfg(test)]
+mod optimizer_tests {
+    use crate::optimizer::*;
+
+    fn optimized_kinds(command: &str) -> Vec<OpKind> {
+        let program = optimize(Program::parse(command).unwrap(), OptimizationLevel::Idioms, CellWidth::Bits8, CellOverflow::Wrap, BoundsPolicy::Wrap);
+
+        program.ops.into_iter().map(|op| op.kind).collect()
+    }
+
+    #[test]
+    fn clear_loops() {
+        assert_eq!(optimized_kinds("[-]"), vec![OpKind::Clear]);
+        assert_eq!(optimized_kinds("[+]"), vec![OpKind::Clear]);
+    }
+
+    #[test]
+    fn scan_loops() {
+        assert_eq!(optimized_kinds("[>]"), vec![OpKind::Scan(1)]);
+        assert_eq!(optimized_kinds("[<<]"), vec![OpKind::Scan(-2)]);
+    }
+
+    #[test]
+    fn multiply_loops() {
+        assert_eq!(optimized_kinds("[->+>++<<]"), vec![OpKind::MulAdd(vec![(1, 1), (2, 2)])]);
+        assert_eq!(optimized_kinds("[<--->-]"), vec![OpKind::MulAdd(vec![(-1, -3)])]);
+    }
+
+    #[test]
+    fn unbalanced_loops_are_kept() {
+        // the head does not return to the counter cell
+        assert!(matches!(optimized_kinds("[->+]")[0], OpKind::Loop(_)));
+        // the counter is not decremented by one
+        assert!(matches!(optimized_kinds("[-->+<]")[0], OpKind::Loop(_)));
+        // loops with i/o are never replaced
+        assert!(matches!(optimized_kinds("[-.]")[0], OpKind::Loop(_)));
+    }
+
+    #[test]
+    fn nested_idioms() {
+        let kinds = optimized_kinds("+[>[-]<-]");
+
+        match &kinds[1] {
+            OpKind::Loop(body) => assert_eq!(body[1].kind, OpKind::Clear),
+            _ => panic!("expected a loop"),
+        }
+    }
+
+    #[test]
+    fn no_optimization() {
+        let program = optimize(Program::parse("[-]").unwrap(), OptimizationLevel::None, CellWidth::Bits8, CellOverflow::Wrap, BoundsPolicy::Wrap);
+
+        assert!(matches!(program.ops[0].kind, OpKind::Loop(_)));
+    }
+
+    #[test]
+    fn loops_without_wrapping_cells() {
+        for (cell_width, cell_overflow) in [(CellWidth::Bits8, CellOverflow::Error), (CellWidth::Unbounded, CellOverflow::Wrap)] {
+            let program = optimize(Program::parse("[-][->+<][>]").unwrap(), OptimizationLevel::Idioms, cell_width, cell_overflow, BoundsPolicy::Wrap);
+
+            assert!(matches!(program.ops[0].kind, OpKind::Loop(_)));
+            assert!(matches!(program.ops[1].kind, OpKind::Loop(_)));
+            // scans don't change any cell
+            assert_eq!(program.ops[2].kind, OpKind::Scan(1));
+        }
+    }
+
+    #[test]
+    fn multiply_loops_with_clamped_head() {
+        let program = optimize(Program::parse("[-][->+<]").unwrap(), OptimizationLevel::Idioms, CellWidth::Bits8, CellOverflow::Wrap,
+                               BoundsPolicy::Clamp);
+
+        assert_eq!(program.ops[0].kind, OpKind::Clear);
+        assert!(matches!(program.ops[1].kind, OpKind::Loop(_)));
+    }
+}
@@ -0,0 +1,158 @@
+use crate::program::*;
+
+use std::io::{self, Write};
+
+// longest loop source shown in the report
+const EXCERPT_LENGTH: usize = 40;
+
+/// Execution counts of one loop.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoopProfile {
+    /// Position of the `[`.
+    pub start: usize,
+    /// Position right after the matching `]`.
+    pub end: usize,
+    /// Times the loop was reached.
+    pub entries: u64,
+    /// Times its body was started.
+    pub iterations: u64,
+    /// Instructions executed inside the loop, the brackets included.
+    pub steps: u64,
+}
+
+/// Counts collected by a profiled run.
+#[derive(Clone, Debug)]
+pub struct Profile {
+    source: Vec<char>,
+    /// Executions of every position in the source, comments stay at zero.
+    pub position_counts: Vec<u64>,
+    /// Loops in the order of their `[`.
+    pub loops: Vec<LoopProfile>,
+    pub total_steps: u64,
+}
+
+impl Profile {
+    pub fn new(source: Vec<char>, position_counts: Vec<u64>, loops: Vec<LoopProfile>, total_steps: u64) -> Profile {
+        Profile {
+            source,
+            position_counts,
+            loops,
+            total_steps,
+        }
+    }
+
+    pub fn source(&self) -> &[char] {
+        &self.source
+    }
+
+    fn excerpt(&self, start: usize, end: usize) -> String {
+        let text: String = self.source[start..end].iter()
+            .map(|c| if c.is_whitespace() { ' ' } else { *c })
+            .collect();
+
+        if text.chars().count() > EXCERPT_LENGTH {
+            text.chars().take(EXCERPT_LENGTH - 3).collect::<String>() + "..."
+        } else {
+            text
+        }
+    }
+
+    /// Loops ordered from the one most steps were spent in.
+    pub fn hottest_loops(&self) -> Vec<&LoopProfile> {
+        let mut loops: Vec<&LoopProfile> = self.loops.iter().collect();
+        loops.sort_by(|first, second| second.steps.cmp(&first.steps).then(first.start.cmp(&second.start)));
+
+        loops
+    }
+
+    /// Total steps and the max_loops hottest loops with their source.
+    pub fn report(&self, max_loops: usize) -> String {
+        let mut report = String::from("Total steps: ") + &self.total_steps.to_string() + "\n";
+
+        let loops = self.hottest_loops();
+        if loops.is_empty() {
+            return report + "No loops.\n";
+        }
+
+        report += "\nHottest loops:\n";
+        report += "  rank       steps      %  iterations   entries  line:col  source\n";
+
+        for (rank, profile) in loops.iter().take(max_loops).enumerate() {
+            let (line, column) = Span { start: profile.start, end: profile.start + 1 }.line_and_column(&self.source);
+            let share = if self.total_steps == 0 { 0.0 } else { profile.steps as f64 * 100.0 / self.total_steps as f64 };
+
+            report += &format!("{:>6} {:>11} {:>6.1} {:>11} {:>9} {:>9}  {}\n",
+                               rank + 1,
+                               profile.steps,
+                               share,
+                               profile.iterations,
+                               profile.entries,
+                               line.to_string() + ":" + &column.to_string(),
+                               self.excerpt(profile.start, profile.end));
+        }
+
+        report
+    }
+
+    /// One row for every instruction in the source, loops also get their iterations.
+    pub fn write_csv(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "offset,line,column,instruction,count,iterations")?;
+
+        for (position, c) in self.source.iter().enumerate() {
+            if !"+-<>[].,".contains(*c) {
+                continue;
+            }
+
+            let (line, column) = Span { start: position, end: position + 1 }.line_and_column(&self.source);
+            let iterations = match self.loops.iter().find(|profile| profile.start == position) {
+                Some(profile) => profile.iterations.to_string(),
+                None => String::new(),
+            };
+
+            writeln!(out, "{},{},{},\"{}\",{},{}", position, line, column, c, self.position_counts[position], iterations)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod profiler_tests {
+    use crate::profiler::*;
+
+    fn profile() -> Profile {
+        let source: Vec<char> = "+[-]\n,".chars().collect();
+        let loops = vec![LoopProfile { start: 1, end: 4, entries: 1, iterations: 1, steps: 3 }];
+
+        Profile::new(source, vec![1, 1, 1, 1, 0, 1], loops, 5)
+    }
+
+    #[test]
+    fn report() {
+        let report = profile().report(10);
+
+        assert!(report.starts_with("Total steps: 5\n"));
+        assert!(report.contains("     1           3   60.0           1         1       1:2  [-]"));
+    }
+
+    #[test]
+    fn csv() {
+        let mut out = Vec::new();
+        profile().write_csv(&mut out).unwrap();
+
+        let csv = String::from_utf8(out).unwrap();
+        let rows: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(rows.len(), 6);
+        assert_eq!(rows[2], "1,1,2,\"[\",1,1");
+        assert_eq!(rows[5], "5,2,1,\",\",1,");
+    }
+
+    #[test]
+    fn long_excerpt() {
+        let source: Vec<char> = std::iter::repeat_n('+', 100).collect();
+        let profile = Profile::new(source, vec![0; 100], Vec::new(), 0);
+
+        assert_eq!(profile.excerpt(0, 100).len(), EXCERPT_LENGTH);
+    }
+}
@@ -0,0 +1,177 @@
+use crate::bracket_validator::*;
+
+/// range of command characters an operation was parsed from, end excluded
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum OpKind {
+    /// a run of + or of - collapsed into a single change of the current cell
+    Add(i32),
+    /// a run of > or of < collapsed into a single head move
+    Move(isize),
+    Loop(Vec<Op>),
+    In,
+    Out,
+
+    // produced by the optimizer in place of whole loops
+    /// set the current cell to zero
+    Clear,
+    /// move by the given distance until a zero cell is reached
+    Scan(isize),
+    /// add the current cell times factor to the cell at each (offset, factor), then clear it
+    MulAdd(Vec<(isize, i32)>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Op {
+    pub kind: OpKind,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug)]
+pub struct Program {
+    pub ops: Vec<Op>,
+    /// the command spans point into
+    pub source: Vec<char>,
+}
+
+impl Span {
+    /// line and column of the span start, both counted from 1
+    pub fn line_and_column(&self, source: &[char]) -> (usize, usize) {
+        let line_start = source[..self.start]
+            .iter()
+            .rposition(|ch| *ch == '\n')
+            .map_or(0, |pos| pos + 1);
+
+        let line = source[..line_start].iter().filter(|ch| **ch == '\n').count() + 1;
+
+        (line, self.start - line_start + 1)
+    }
+}
+
+impl Op {
+    fn new(kind: OpKind, start: usize, end: usize) -> Op {
+        Op {
+            kind,
+            span: Span { start, end },
+        }
+    }
+}
+
+// number of times the first character repeats at the start of the command
+fn run_length(command: &[char]) -> usize {
+    command.iter().take_while(|ch| **ch == command[0]).count()
+}
+
+impl Program {
+    pub fn parse(source: &str) -> Result<Program, BracketError> {
+        let command: Vec<char> = source.chars().collect();
+        validate_brackets(&command)?;
+
+        // ops of every loop that is still open together with its [ position
+        let mut opened_loops: Vec<(Vec<Op>, usize)> = Vec::new();
+        let mut ops: Vec<Op> = Vec::new();
+
+        let mut pos = 0;
+        while pos < command.len() {
+            let start = pos;
+
+            match command[pos] {
+                '+' | '-' => {
+                    // only runs going one way are collapsed, so every cell overflow
+                    // and tape edge a run reaches is still reached
+                    let amount: i32 = if command[pos] == '+' { 1 } else { -1 };
+                    let length = run_length(&command[pos..]);
+                    pos += length;
+
+                    ops.push(Op::new(OpKind::Add(amount * length as i32), start, pos));
+                    continue;
+                },
+                '>' | '<' => {
+                    let distance: isize = if command[pos] == '>' { 1 } else { -1 };
+                    let length = run_length(&command[pos..]);
+                    pos += length;
+
+                    ops.push(Op::new(OpKind::Move(distance * length as isize), start, pos));
+                    continue;
+                },
+                '[' => {
+                    opened_loops.push((ops, pos));
+                    ops = Vec::new();
+                },
+                ']' => {
+                    // brackets are validated, so there is always an opened loop
+                    let (mut outer_ops, open_pos) = opened_loops.pop().unwrap();
+                    outer_ops.push(Op::new(OpKind::Loop(ops), open_pos, pos + 1));
+
+                    ops = outer_ops;
+                },
+                ',' => ops.push(Op::new(OpKind::In, pos, pos + 1)),
+                '.' => ops.push(Op::new(OpKind::Out, pos, pos + 1)),
+                _ => (),
+            };
+
+            pos += 1;
+        }
+
+        Ok(Program { ops, source: command })
+    }
+}
+
+#[cfg(test)]
+mod program_tests {
+    use crate::program::*;
+
+    #[test]
+    fn collapse_runs() {
+        let program = Program::parse("+++-->><<<.").unwrap();
+
+        assert_eq!(program.ops, vec![Op::new(OpKind::Add(3), 0, 3), Op::new(OpKind::Add(-2), 3, 5),
+                                     Op::new(OpKind::Move(2), 5, 7), Op::new(OpKind::Move(-3), 7, 10),
+                                     Op::new(OpKind::Out, 10, 11)]);
+    }
+
+    #[test]
+    fn comments_split_runs() {
+        let program = Program::parse("++ +").unwrap();
+
+        assert_eq!(program.ops, vec![Op::new(OpKind::Add(2), 0, 2), Op::new(OpKind::Add(1), 3, 4)]);
+    }
+
+    #[test]
+    fn nested_loops() {
+        let program = Program::parse(",[>[-]<]").unwrap();
+
+        assert_eq!(program.ops.len(), 2);
+        assert_eq!(program.ops[0].kind, OpKind::In);
+
+        let outer = &program.ops[1];
+        assert_eq!(outer.span, Span { start: 1, end: 8 });
+
+        match &outer.kind {
+            OpKind::Loop(body) => {
+                assert_eq!(body.len(), 3);
+                assert_eq!(body[1], Op::new(OpKind::Loop(vec![Op::new(OpKind::Add(-1), 4, 5)]), 3, 6));
+            },
+            _ => panic!("expected a loop"),
+        }
+    }
+
+    #[test]
+    fn span_location() {
+        let program = Program::parse("+\n\t>[-]").unwrap();
+
+        assert_eq!(program.ops[0].span.line_and_column(&program.source), (1, 1));
+        assert_eq!(program.ops[2].span.line_and_column(&program.source), (2, 3));
+    }
+
+    #[test]
+    fn unbalanced_brackets() {
+        assert!(Program::parse("[").is_err());
+        assert!(Program::parse("]").is_err());
+    }
+}
@@ -0,0 +1,223 @@
+use crate::config::*;
+use crate::input_source::*;
+use crate::interpreter::*;
+use crate::memory_tape::*;
+use crate::optimizer::*;
+
+use std::collections::HashMap;
+use std::fs;
+
+// first line of every snapshot file, the number changes with the format
+const FORMAT_HEADER: &str = "bf-snapshot 1";
+
+/// State of a run at one step, which can be saved to a file and continued
+/// later with `Interpreter::from_snapshot`.
+///
+/// History, profiling counts and breakpoints are not part of it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    /// Settings of the run, the step and time limits are never saved.
+    pub config: Config,
+    pub source: String,
+    /// Position of the next instruction in the source, its length once the program finished.
+    pub position: usize,
+    /// Positions of the `[` of every loop around the next instruction, outermost first.
+    pub loop_stack: Vec<usize>,
+    /// Cells the head reached, the first one has index `tape_start`.
+    pub tape: Vec<i64>,
+    pub tape_start: isize,
+    pub head_position: isize,
+    /// Input bytes not read by the program yet.
+    pub pending_input: Vec<u8>,
+    /// Whether `pending_input` is all the input left, it isn't when reading from stdin.
+    pub input_ended: bool,
+    /// Every byte written by `.` so far.
+    pub output: Vec<u8>,
+    pub statistics: Statistics,
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.is_ascii() || !text.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..text.len()).step_by(2)
+        .map(|pos| u8::from_str_radix(&text[pos..pos + 2], 16).ok())
+        .collect()
+}
+
+fn join_numbers<T: ToString>(numbers: &[T]) -> String {
+    numbers.iter().map(|number| number.to_string()).collect::<Vec<String>>().join(" ")
+}
+
+fn parse_numbers<T: std::str::FromStr>(text: &str) -> Option<Vec<T>> {
+    text.split_whitespace().map(|number| number.parse().ok()).collect()
+}
+
+impl Snapshot {
+    /// Text saved to a snapshot file, the program source comes last as it is.
+    pub fn to_text(&self) -> String {
+        let fields = [
+            ("optimization", String::from(self.config.optimization.name())),
+            ("eof", String::from(self.config.eof.name())),
+            ("cell-width", String::from(self.config.cell_width.name())),
+            ("cell-overflow", String::from(self.config.cell_overflow.name())),
+            ("tape-bounds", self.config.tape_bounds.lowest.to_string() + ":" + &self.config.tape_bounds.highest.to_string()),
+            ("bounds-policy", String::from(self.config.bounds_policy.name())),
+            ("position", self.position.to_string()),
+            ("loop-stack", join_numbers(&self.loop_stack)),
+            ("tape-start", self.tape_start.to_string()),
+            ("head", self.head_position.to_string()),
+            ("tape", join_numbers(&self.tape)),
+            ("steps", self.statistics.steps.to_string()),
+            ("input-bytes", self.statistics.input_bytes.to_string()),
+            ("output-bytes", self.statistics.output_bytes.to_string()),
+            ("pending-input", to_hex(&self.pending_input)),
+            ("input-ended", self.input_ended.to_string()),
+            ("output", to_hex(&self.output)),
+        ];
+
+        let mut text = String::from(FORMAT_HEADER) + "\n";
+        for (key, value) in fields {
+            text = text + key + " " + &value + "\n";
+        }
+
+        text + "source\n" + &self.source
+    }
+
+    fn parse_text(text: &str) -> Option<Snapshot> {
+        let (header, source) = text.split_once("\nsource\n")?;
+        let mut lines = header.lines();
+
+        if lines.next()? != FORMAT_HEADER {
+            return None;
+        }
+
+        let mut fields = HashMap::new();
+        for line in lines {
+            // lists and bytes can be empty
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            fields.insert(key, value);
+        }
+
+        let config = Config {
+            optimization: OptimizationLevel::from_name(fields.get("optimization")?)?,
+            eof: EofBehavior::from_name(fields.get("eof")?)?,
+            cell_width: CellWidth::from_name(fields.get("cell-width")?)?,
+            cell_overflow: CellOverflow::from_name(fields.get("cell-overflow")?)?,
+            tape_bounds: TapeBounds::from_range(fields.get("tape-bounds")?)?,
+            bounds_policy: BoundsPolicy::from_name(fields.get("bounds-policy")?)?,
+            ..Config::default()
+        };
+
+        Some(Snapshot {
+            config,
+            source: String::from(source),
+            position: fields.get("position")?.parse().ok()?,
+            loop_stack: parse_numbers(fields.get("loop-stack")?)?,
+            tape: parse_numbers(fields.get("tape")?)?,
+            tape_start: fields.get("tape-start")?.parse().ok()?,
+            head_position: fields.get("head")?.parse().ok()?,
+            pending_input: from_hex(fields.get("pending-input")?)?,
+            input_ended: fields.get("input-ended")?.parse().ok()?,
+            output: from_hex(fields.get("output")?)?,
+            statistics: Statistics {
+                steps: fields.get("steps")?.parse().ok()?,
+                input_bytes: fields.get("input-bytes")?.parse().ok()?,
+                output_bytes: fields.get("output-bytes")?.parse().ok()?,
+            },
+        })
+    }
+
+    pub fn from_text<'a>(text: &str) -> Result<Snapshot, &'a str> {
+        Snapshot::parse_text(text).ok_or("Invalid snapshot file.")
+    }
+
+    pub fn read_from_file<'a>(file_name: &str) -> Result<Snapshot, &'a str> {
+        match fs::read_to_string(file_name) {
+            Ok(text) => Snapshot::from_text(&text),
+            Err(_) => Err("Couldn't read file."),
+        }
+    }
+
+    pub fn write_to_file<'a>(&self, file_name: &str) -> Result<(), &'a str> {
+        fs::write(file_name, self.to_text()).map_err(|_| "Couldn't write to file.")
+    }
+}
+
+#[cfg(test)]
+mod snapshot_tests {
+    use crate::snapshot::*;
+    use crate::program::*;
+    use std::io;
+
+    fn interpreter(command: &str, input: InputSource, config: &Config) -> Interpreter {
+        let mut interpreter = Interpreter::new(false, config, input, Box::new(io::sink()));
+        interpreter.load(&Program::parse(command).unwrap());
+
+        interpreter
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let config = Config { cell_width: CellWidth::Bits16, tape_bounds: TapeBounds::bidirectional(100), ..Config::default() };
+        let mut interpreter = interpreter("+[,.<]\nsource\n", InputSource::from_bytes(b"ab\n"), &config);
+        for _ in 0..4 {
+            interpreter.step().unwrap();
+        }
+
+        let snapshot = interpreter.snapshot();
+        assert_eq!(snapshot.pending_input, b"b\n");
+        assert_eq!(snapshot.loop_stack, [1]);
+
+        assert_eq!(Snapshot::from_text(&snapshot.to_text()).unwrap(), snapshot);
+        assert!(Snapshot::from_text("bf-snapshot 1\nposition 0\nsource\n").is_err());
+        assert!(Snapshot::from_text(&snapshot.to_text().replace("pending-input 620a", "pending-input 62x")).is_err());
+    }
+
+    #[test]
+    fn resume_run() {
+        let command = ",[>+++[->++<]<-.,]";
+        let config = Config { step_limit: Some(10), ..Config::default() };
+
+        let mut whole = interpreter(command, InputSource::from_bytes(b"\x03\x02"), &Config::default());
+        whole.run().unwrap();
+
+        let mut first = interpreter(command, InputSource::from_bytes(b"\x03\x02"), &config);
+        assert_eq!(first.run().unwrap(), RunStatus::StepLimitExceeded);
+
+        let snapshot = Snapshot::from_text(&first.snapshot().to_text()).unwrap();
+        assert!(snapshot.input_ended);
+
+        let mut resumed = Interpreter::from_snapshot(false, &snapshot, InputSource::from_bytes(b""), Box::new(io::sink())).unwrap();
+        assert_eq!(resumed.current_position(), first.current_position());
+        assert_eq!(resumed.run().unwrap(), RunStatus::Finished);
+
+        let (expected, result) = (whole.result(), resumed.result());
+        assert_eq!(result.output, expected.output);
+        assert_eq!(result.tape, expected.tape);
+        assert_eq!(result.head_position, expected.head_position);
+        assert_eq!(result.statistics, expected.statistics);
+
+        // the snapshot was taken by the first interpreter, which can still go on 10 steps at a time
+        while first.run().unwrap() == RunStatus::StepLimitExceeded {}
+        assert_eq!(first.result().output, expected.output);
+        assert_eq!(first.result().statistics, expected.statistics);
+    }
+
+    #[test]
+    fn reject_other_program() {
+        let mut snapshot = interpreter("+[-]", InputSource::from_bytes(b""), &Config::default()).snapshot();
+        snapshot.position = 2;
+
+        assert!(Interpreter::from_snapshot(false, &snapshot, InputSource::Stdin, Box::new(io::sink())).is_err());
+
+        snapshot.position = 0;
+        snapshot.tape = vec![300];
+        assert!(Interpreter::from_snapshot(false, &snapshot, InputSource::Stdin, Box::new(io::sink())).is_err());
+    }
+}